@@ -0,0 +1,34 @@
+import { describe, expect, test } from "vitest";
+
+import * as array2d from "./array2d";
+
+// A 2x3 array with distinct values, so any misplaced cell shows up.
+const arr2d = array2d.from([1, 2, 3, 4, 5, 6], 2, 3);
+
+describe("rotate", () => {
+    test("turns clockwise by quarter turns", () => {
+        expect(array2d.rotate(arr2d, 1)).toEqual(array2d.rot90(arr2d));
+        expect(array2d.rotate(arr2d, 2)).toEqual(
+            array2d.rot90(array2d.rot90(arr2d))
+        );
+        expect(array2d.rotate(arr2d, 3)).toEqual(
+            array2d.rot90(array2d.rot90(array2d.rot90(arr2d)))
+        );
+    });
+
+    test("returns the original array for whole turns", () => {
+        expect(array2d.rotate(arr2d, 0)).toBe(arr2d);
+        expect(array2d.rotate(arr2d, 4)).toBe(arr2d);
+        expect(array2d.rotate(arr2d, -4)).toBe(arr2d);
+    });
+
+    test("accepts negative turns", () => {
+        expect(array2d.rotate(arr2d, -1)).toEqual(array2d.rotate(arr2d, 3));
+        expect(array2d.rotate(arr2d, -3)).toEqual(array2d.rot90(arr2d));
+    });
+
+    test("accepts more than a whole turn", () => {
+        expect(array2d.rotate(arr2d, 5)).toEqual(array2d.rot90(arr2d));
+        expect(array2d.rotate(arr2d, 6)).toEqual(array2d.rotate(arr2d, 2));
+    });
+});
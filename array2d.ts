@@ -44,6 +44,29 @@ export function subarray<T>(
     return subarr2d;
 }
 
+export function hstack<T>(l: Array2D<T>, r: Array2D<T>): Array2D<T> | null {
+    if (l.nrows != r.nrows) {
+        return null;
+    }
+    const stacked = Array2D<T>(l.nrows, l.ncols + r.ncols);
+    for (let y = 0; y < l.nrows; ++y) {
+        for (let x = 0; x < l.ncols; ++x) {
+            stacked[y * stacked.ncols + x] = l[y * l.ncols + x];
+        }
+        for (let x = 0; x < r.ncols; ++x) {
+            stacked[y * stacked.ncols + l.ncols + x] = r[y * r.ncols + x];
+        }
+    }
+    return stacked;
+}
+
+export function vstack<T>(l: Array2D<T>, r: Array2D<T>): Array2D<T> | null {
+    if (l.ncols != r.ncols) {
+        return null;
+    }
+    return from([...l, ...r], l.nrows + r.nrows, l.ncols);
+}
+
 export function transpose<T>(arr2d: Array2D<T>) {
     const transposed = Array2D<T>(arr2d.ncols, arr2d.nrows);
     for (let y = 0; y < arr2d.nrows; ++y) {
@@ -72,6 +95,47 @@ export function rot90<T>(arr2d: Array2D<T>) {
     return transposed;
 }
 
+// Rotates clockwise by a quarter turn, also returning where the given cell ends up.
+export function rot90WithOrigin<T>(
+    arr2d: Array2D<T>,
+    origin: { x: number; y: number }
+): [Array2D<T>, { x: number; y: number }] {
+    return [rot90(arr2d), { x: arr2d.nrows - 1 - origin.y, y: origin.x }];
+}
+
+export function rot270<T>(arr2d: Array2D<T>) {
+    const rotated = Array2D<T>(arr2d.ncols, arr2d.nrows);
+    for (let y = 0; y < arr2d.nrows; ++y) {
+        for (let x = 0; x < arr2d.ncols; ++x) {
+            rotated[(arr2d.ncols - x - 1) * rotated.ncols + y] =
+                arr2d[y * arr2d.ncols + x];
+        }
+    }
+    return rotated;
+}
+
+// Rotates clockwise by num quarter turns, which may be negative. Returns the original array if no rotation is needed.
+export function rotate<T>(arr2d: Array2D<T>, num: number): Array2D<T> {
+    switch (((num % 4) + 4) % 4) {
+        case 1:
+            return rot90(arr2d);
+        case 2:
+            return from([...arr2d].reverse(), arr2d.nrows, arr2d.ncols);
+        case 3:
+            return rot270(arr2d);
+        default:
+            return arr2d;
+    }
+}
+
+export function invert(arr2d: Array2D<boolean>): Array2D<boolean> {
+    const inverted = Array2D<boolean>(arr2d.nrows, arr2d.ncols);
+    for (let i = 0; i < arr2d.length; ++i) {
+        inverted[i] = !arr2d[i];
+    }
+    return inverted;
+}
+
 export function equal<T>(l: Array2D<T>, r: Array2D<T>) {
     if (l.nrows != r.nrows || l.ncols != r.ncols) {
         return false;
@@ -84,6 +148,24 @@ export function equal<T>(l: Array2D<T>, r: Array2D<T>) {
     return true;
 }
 
+export function diff<T>(
+    l: Array2D<T>,
+    r: Array2D<T>
+): { x: number; y: number }[] | null {
+    if (l.nrows != r.nrows || l.ncols != r.ncols) {
+        return null;
+    }
+    const positions: { x: number; y: number }[] = [];
+    for (let y = 0; y < l.nrows; ++y) {
+        for (let x = 0; x < l.ncols; ++x) {
+            if (l[y * l.ncols + x] != r[y * r.ncols + x]) {
+                positions.push({ x, y });
+            }
+        }
+    }
+    return positions;
+}
+
 export function pretty<T extends { toString: () => string }>(
     arr2d: Array2D<T>
 ): string {
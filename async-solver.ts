@@ -10,7 +10,9 @@ export default class AsyncSolver {
         parts: Part[],
         requirements: Requirement[],
         gridSettings: GridSettings,
-        spinnableColors: boolean[]
+        spinnableColors: boolean[],
+        // How many solutions to fetch from the worker per message.
+        chunkSize: number = 1
     ) {
         const worker = new Worker(new URL("./worker.ts", import.meta.url), {
             type: "module",
@@ -44,16 +46,22 @@ export default class AsyncSolver {
                             worker.removeEventListener("message", eh);
                             resolve(e);
                         });
-                        worker.postMessage({ type: "next" } as Request);
+                        worker.postMessage({
+                            type: "nextChunk",
+                            n: chunkSize,
+                        } as Request);
                     }
                 );
-                if (e.data.type != "next") {
+                if (e.data.type == "solveError") {
+                    throw e.data.error;
+                }
+                if (e.data.type != "nextChunk") {
                     throw "not ready";
                 }
-                if (e.data.done) {
+                yield* e.data.values;
+                if (e.data.values.length < chunkSize) {
                     break;
                 }
-                yield e.data.value;
             }
         })();
     }
@@ -0,0 +1,1701 @@
+import { describe, expect, test } from "vitest";
+
+import * as array2d from "./array2d";
+import bn3 from "./data/bn3.json";
+import bn6 from "./data/bn6.json";
+import {
+    adjacencyGraph,
+    colorHistogram,
+    CommandLineRule,
+    dedupRequirements,
+    DEFAULT_COMMAND_LINE_MATRIX,
+    expandDeduped,
+    exportCandidates,
+    exportNative,
+    importNative,
+    maskFromRomBytes,
+    placeAll,
+    placementBugReport,
+    placementCells,
+    placementReasons,
+    scoreSolution,
+    searchEstimate,
+    solutionsEquivalent,
+    solve,
+    solveContaining,
+    Solver,
+    SteppedSolver,
+    validateArgs,
+    validatePart,
+    validateParts,
+    wallIds,
+    wildcardPart,
+} from "./solver";
+
+import type {
+    Constraint,
+    GridSettings,
+    Part,
+    Position,
+    Requirement,
+    Solution,
+    SolveStats,
+} from "./solver";
+
+const gridSettings: GridSettings = {
+    height: 4,
+    width: 4,
+    hasOob: false,
+    commandLineRow: 1,
+};
+
+// Builds a grid-sized mask from rows of "x" (filled) and "." (empty), starting at the top left.
+function mask(
+    rows: string[],
+    gs: GridSettings = gridSettings
+): array2d.Array2D<boolean> {
+    const m = array2d.full(false, gs.height, gs.width);
+    for (let y = 0; y < rows.length; ++y) {
+        for (let x = 0; x < rows[y].length; ++x) {
+            m[y * gs.width + x] = rows[y][x] == "x";
+        }
+    }
+    return m;
+}
+
+function part(
+    isSolid: boolean,
+    color: number,
+    rows: string[],
+    gs: GridSettings = gridSettings
+): Part {
+    return {
+        isSolid,
+        color,
+        compressedMask: mask(rows, gs),
+        uncompressedMask: mask(rows, gs),
+    };
+}
+
+function req(partIndex: number, constraint: Partial<Constraint> = {}) {
+    return {
+        partIndex,
+        constraint: {
+            compressed: null,
+            onCommandLine: null,
+            minBugLevel: 0,
+            maxBugLevel: Infinity,
+            ...constraint,
+        },
+    };
+}
+
+// Parts of different sizes, so a placement given for the wrong requirement shows up as the wrong number of cells.
+const parts = [
+    part(true, 0, ["xxx", ".x."]),
+    part(true, 1, ["x.", "x.", "xx"]),
+    part(false, 2, ["xx"]),
+    part(true, 2, ["x"]),
+];
+const spinnableColors = [true, true, false];
+
+function bugCount(requirements: Requirement[], solution: Solution) {
+    return placementBugReport(
+        parts,
+        requirements,
+        solution,
+        gridSettings
+    ).filter(
+        (b) => b.outOfBounds || b.commandLineBugged || b.touchingSameColor
+    ).length;
+}
+
+function sorted(solutions: Solution[]): string[] {
+    return solutions.map((solution) => JSON.stringify(solution)).sort();
+}
+
+// Converts the parts of a game's data file like the app does.
+function gameData(raw: typeof bn3 | typeof bn6) {
+    const { height, width } = raw.gridSettings;
+    return {
+        parts: raw.parts.map(
+            (p): Part => ({
+                isSolid: p.isSolid,
+                color: p.color,
+                compressedMask: array2d.from(
+                    p.compressedMask.map((v) => !!v),
+                    height,
+                    width
+                ),
+                uncompressedMask: array2d.from(
+                    p.uncompressedMask.map((v) => !!v),
+                    height,
+                    width
+                ),
+            })
+        ),
+        gridSettings: raw.gridSettings as GridSettings,
+        spinnableColors: raw.colors.map(() => false),
+    };
+}
+
+function newStats(): SolveStats {
+    return {
+        nodes: 0,
+        nodeLimitHit: false,
+        depth: 0,
+        deepestRequirement: null,
+        firstLevelTried: 0,
+        firstLevelTotal: 0,
+    };
+}
+
+function count(solutions: Iterable<Solution>): number {
+    let n = 0;
+    for (const _ of solutions) {
+        ++n;
+    }
+    return n;
+}
+
+describe("solve", () => {
+    test("yields a single empty solution with no requirements", () => {
+        expect([...solve(parts, [], gridSettings, spinnableColors)]).toEqual([
+            [],
+        ]);
+    });
+
+    // The counts are the ones found when the search still cloned the grid for every candidate, so placing and undoing in place must leave the grid as it was.
+    test("finds the same solutions as cloning the grid", () => {
+        expect(
+            [...solve(parts, [req(0), req(1)], gridSettings, spinnableColors)]
+                .length
+        ).toBe(148);
+        expect(
+            [
+                ...solve(
+                    parts,
+                    [req(0), req(1), req(2)],
+                    gridSettings,
+                    spinnableColors
+                ),
+            ].length
+        ).toBe(450);
+        expect(
+            [
+                ...solve(
+                    parts,
+                    [req(0, { onCommandLine: true }), req(2), req(3)],
+                    gridSettings,
+                    spinnableColors
+                ),
+            ].length
+        ).toBe(1320);
+    });
+
+    test("yields placements in requirement order", () => {
+        const requirements = [req(2), req(0), req(3), req(1)];
+        const sizes = requirements.map((r) =>
+            parts[r.partIndex].compressedMask.filter((v) => v).length
+        );
+        let n = 0;
+        for (const solution of solve(
+            parts,
+            requirements,
+            gridSettings,
+            spinnableColors
+        )) {
+            const cells = placeAll(parts, requirements, solution, gridSettings);
+            expect(
+                requirements.map((_, i) => cells.filter((v) => v === i).length)
+            ).toEqual(sizes);
+            if (++n == 100) {
+                break;
+            }
+        }
+        expect(n).toBe(100);
+    });
+
+    test("yields the same solutions in the same order for reordered requirements", () => {
+        const requirements = [req(0), req(1), req(2), req(3)];
+        const expected = [
+            ...solve(parts, requirements, gridSettings, spinnableColors),
+        ];
+        for (const perm of [
+            [3, 2, 1, 0],
+            [2, 0, 3, 1],
+        ]) {
+            const solutions = [
+                ...solve(
+                    parts,
+                    perm.map((i) => requirements[i]),
+                    gridSettings,
+                    spinnableColors
+                ),
+            ].map((solution) => {
+                const unpermuted: Solution = new Array(solution.length);
+                perm.forEach((i, j) => {
+                    unpermuted[i] = solution[j];
+                });
+                return unpermuted;
+            });
+            expect(solutions).toEqual(expected);
+        }
+    });
+
+    test("yields the same solutions in the same order for reordered parts", () => {
+        const requirements = [req(0), req(1), req(2), req(3)];
+        const expected = [
+            ...solve(parts, requirements, gridSettings, spinnableColors),
+        ];
+        for (const perm of [
+            [3, 2, 1, 0],
+            [2, 0, 3, 1],
+        ]) {
+            expect([
+                ...solve(
+                    perm.map((i) => parts[i]),
+                    requirements.map((r) => ({
+                        ...r,
+                        partIndex: perm.indexOf(r.partIndex),
+                    })),
+                    gridSettings,
+                    spinnableColors
+                ),
+            ]).toEqual(expected);
+        }
+    });
+
+    test("maxBugs limits the number of bugged placements", () => {
+        const requirements = [req(0), req(1), req(2)];
+        const all = [
+            ...solve(parts, requirements, gridSettings, spinnableColors),
+        ];
+
+        const bugFree = [
+            ...solve(
+                parts,
+                requirements.map((r) =>
+                    req(r.partIndex, { maxBugLevel: 0 })
+                ),
+                gridSettings,
+                spinnableColors
+            ),
+        ];
+        expect(
+            sorted([
+                ...solve(parts, requirements, gridSettings, spinnableColors, {
+                    maxBugs: 0,
+                }),
+            ])
+        ).toEqual(sorted(bugFree));
+
+        const oneBug = [
+            ...solve(parts, requirements, gridSettings, spinnableColors, {
+                maxBugs: 1,
+            }),
+        ];
+        expect(sorted(oneBug)).toEqual(
+            sorted(
+                all.filter((solution) => bugCount(requirements, solution) <= 1)
+            )
+        );
+        expect(
+            oneBug.some((solution) => bugCount(requirements, solution) == 1)
+        ).toBe(true);
+    });
+});
+
+describe("placeAll", () => {
+    test("returns an empty grid with no placements", () => {
+        expect(placeAll(parts, [], [], gridSettings)).toEqual(
+            new Array(16).fill(null)
+        );
+        const oobGridSettings = { ...gridSettings, hasOob: true };
+        expect(placeAll(parts, [], [], oobGridSettings)).toEqual(
+            new Array(16).fill(null)
+        );
+    });
+
+    test("leaves walls out of the occupancy", () => {
+        const wallGridSettings = {
+            ...gridSettings,
+            walls: [
+                [{ x: 0, y: 0 }, 0],
+                [{ x: 3, y: 3 }, 1],
+            ] as GridSettings["walls"],
+        };
+        const ids = new Array(16).fill(null);
+        ids[0] = 0;
+        ids[15] = 1;
+        expect(wallIds(wallGridSettings)).toEqual(ids);
+
+        const requirements = [req(3)];
+        const solutions = [
+            ...solve(parts, requirements, wallGridSettings, spinnableColors),
+        ];
+        expect(solutions).toHaveLength(14);
+        for (const solution of solutions) {
+            const cells = placeAll(
+                parts,
+                requirements,
+                solution,
+                wallGridSettings
+            );
+            expect(cells[0]).toBeNull();
+            expect(cells[15]).toBeNull();
+            expect(
+                scoreSolution(
+                    parts,
+                    requirements,
+                    solution,
+                    wallGridSettings,
+                    { bugs: 0, rotations: 0, compressed: 0, compactness: 1 }
+                )
+            ).toBe(1);
+        }
+    });
+});
+
+describe("minDistances", () => {
+    const lineGridSettings: GridSettings = {
+        height: 1,
+        width: 3,
+        hasOob: false,
+        commandLineRow: 0,
+    };
+    const lineParts = [
+        part(true, 0, ["x"], lineGridSettings),
+        part(true, 1, ["x"], lineGridSettings),
+    ];
+    const requirements = [req(0), req(0), req(1)];
+
+    // Tries every assignment of requirements to cells, telling apart the requirements with distances to keep and treating the rest as interchangeable copies of their part.
+    function bruteForce(minDistances: [number, number, number][]): number {
+        const distanced = new Set(minDistances.flatMap(([a, b]) => [a, b]));
+        const layouts = new Set<string>();
+        for (let a = 0; a < 3; ++a) {
+            for (let b = 0; b < 3; ++b) {
+                for (let c = 0; c < 3; ++c) {
+                    const xs = [a, b, c];
+                    if (new Set(xs).size < 3) {
+                        continue;
+                    }
+                    if (
+                        minDistances.some(
+                            ([i, j, k]) => Math.abs(xs[i] - xs[j]) < k
+                        )
+                    ) {
+                        continue;
+                    }
+                    const layout = new Array<string>(3);
+                    xs.forEach((x, i) => {
+                        layout[x] = distanced.has(i)
+                            ? `r${i}`
+                            : `p${requirements[i].partIndex}`;
+                    });
+                    layouts.add(layout.join());
+                }
+            }
+        }
+        return layouts.size;
+    }
+
+    test("finds every layout keeping the distance", () => {
+        for (const minDistances of [
+            [[0, 2, 2]],
+            [[0, 1, 2]],
+            [
+                [0, 2, 2],
+                [1, 2, 1],
+            ],
+        ] as [number, number, number][][]) {
+            const solutions = [
+                ...solve(
+                    lineParts,
+                    requirements,
+                    lineGridSettings,
+                    [false, false],
+                    { minDistances }
+                ),
+            ];
+            expect(solutions).toHaveLength(bruteForce(minDistances));
+            for (const solution of solutions) {
+                for (const [i, j, k] of minDistances) {
+                    expect(
+                        Math.abs(
+                            solution[i].loc.position.x -
+                                solution[j].loc.position.x
+                        )
+                    ).not.toBeLessThan(k);
+                }
+            }
+        }
+    });
+});
+
+describe("fillCommandLine", () => {
+    function coversCommandLine(
+        requirements: Requirement[],
+        solution: Solution
+    ) {
+        const cells = placeAll(parts, requirements, solution, gridSettings);
+        return cells
+            .slice(
+                gridSettings.commandLineRow * gridSettings.width,
+                (gridSettings.commandLineRow + 1) * gridSettings.width
+            )
+            .every((v) => v !== null);
+    }
+
+    test("only yields solutions covering the command line", () => {
+        const requirements = [req(0), req(1), req(2)];
+        const all = [
+            ...solve(parts, requirements, gridSettings, spinnableColors),
+        ];
+        const filled = [
+            ...solve(parts, requirements, gridSettings, spinnableColors, {
+                fillCommandLine: true,
+            }),
+        ];
+        expect(filled.length).toBeGreaterThan(0);
+        expect(filled).toEqual(
+            all.filter((solution) => coversCommandLine(requirements, solution))
+        );
+    });
+
+    test("yields nothing without requirements", () => {
+        expect([
+            ...solve(parts, [], gridSettings, spinnableColors, {
+                fillCommandLine: true,
+            }),
+        ]).toEqual([]);
+    });
+});
+
+describe("wildcards", () => {
+    test("fill the cells the other parts leave empty", () => {
+        const lineGridSettings: GridSettings = {
+            height: 1,
+            width: 4,
+            hasOob: false,
+            commandLineRow: 0,
+        };
+        const lineParts = [
+            part(true, 0, ["xx"], lineGridSettings),
+            wildcardPart(lineGridSettings),
+        ];
+        const requirements = [
+            req(0),
+            req(1, { wildcard: true }),
+            req(1, { wildcard: true }),
+        ];
+        const solutions = [
+            ...solve(lineParts, requirements, lineGridSettings, [false]),
+        ];
+        expect(solutions).toHaveLength(3);
+        for (const solution of solutions) {
+            expect(
+                placeAll(lineParts, requirements, solution, lineGridSettings)
+            ).not.toContainEqual(null);
+        }
+    });
+
+    test("may be placed out of bounds", () => {
+        const oobGridSettings: GridSettings = {
+            height: 3,
+            width: 3,
+            hasOob: true,
+            commandLineRow: 1,
+        };
+        // Everything but the forbidden corners.
+        expect([
+            ...solve(
+                [wildcardPart(oobGridSettings)],
+                [req(0, { wildcard: true })],
+                oobGridSettings,
+                []
+            ),
+        ]).toHaveLength(5);
+    });
+});
+
+describe("commandLineRules", () => {
+    // commandLineRow is out of range, as the rules override it.
+    const columnGridSettings: GridSettings = {
+        height: 3,
+        width: 1,
+        hasOob: false,
+        commandLineRow: 4,
+        commandLineRules: [
+            CommandLineRule.SolidPreferred,
+            CommandLineRule.None,
+            CommandLineRule.NonSolidPreferred,
+        ],
+    };
+    const columnParts = [
+        part(true, 0, ["x"], columnGridSettings),
+        part(false, 1, ["x"], columnGridSettings),
+    ];
+
+    test("bug parts touching the row preferring the other kind", () => {
+        const requirements = [req(0), req(1)];
+        const solutions = [
+            ...solve(columnParts, requirements, columnGridSettings, [
+                false,
+                false,
+            ]),
+        ];
+        expect(
+            solutions
+                .map((solution): [number[], boolean[]] => [
+                    solution.map((placement) => placement.loc.position.y),
+                    placementBugReport(
+                        columnParts,
+                        requirements,
+                        solution,
+                        columnGridSettings
+                    ).map((b) => b.commandLineBugged),
+                ])
+                .sort(([a], [b]) => a[0] - b[0] || a[1] - b[1])
+        ).toEqual([
+            [
+                [0, 1],
+                [false, true],
+            ],
+            [
+                [0, 2],
+                [false, false],
+            ],
+            [
+                [1, 0],
+                [true, true],
+            ],
+            [
+                [1, 2],
+                [true, false],
+            ],
+            [
+                [2, 0],
+                [true, true],
+            ],
+            [
+                [2, 1],
+                [true, true],
+            ],
+        ]);
+    });
+
+    // Whether each part is bugged alone in each row of a column with the given rules.
+    function buggedByRow(rules: CommandLineRule[]): boolean[][] {
+        const settings = { ...columnGridSettings, commandLineRules: rules };
+        return [0, 1].map((partIndex) =>
+            [...solve(columnParts, [req(partIndex)], settings, [false, false])]
+                .sort((a, b) => a[0].loc.position.y - b[0].loc.position.y)
+                .map(
+                    (solution) =>
+                        placementBugReport(
+                            columnParts,
+                            [req(partIndex)],
+                            solution,
+                            settings
+                        )[0].commandLineBugged
+                )
+        );
+    }
+
+    test("bug only by the rows preferring non-solid parts without solid ones", () => {
+        expect(
+            buggedByRow([
+                CommandLineRule.None,
+                CommandLineRule.None,
+                CommandLineRule.NonSolidPreferred,
+            ])
+        ).toEqual([
+            [false, false, true],
+            [true, true, false],
+        ]);
+    });
+
+    test("bug only by the rows preferring solid parts without non-solid ones", () => {
+        expect(
+            buggedByRow([
+                CommandLineRule.SolidPreferred,
+                CommandLineRule.None,
+                CommandLineRule.None,
+            ])
+        ).toEqual([
+            [false, true, true],
+            [true, false, false],
+        ]);
+    });
+
+    test("ignore commandLineRow", () => {
+        expect(
+            validateArgs({
+                parts: columnParts,
+                requirements: [req(0), req(1)],
+                gridSettings: columnGridSettings,
+                spinnableColors: [false, false],
+            })
+        ).toEqual([]);
+    });
+});
+
+describe("exportNative", () => {
+    test("round trips through importNative", () => {
+        const requirements = [req(3), req(0), req(3)];
+        let n = 0;
+        for (const solution of solve(
+            parts,
+            requirements,
+            gridSettings,
+            spinnableColors
+        )) {
+            const bytes = exportNative(
+                parts,
+                requirements,
+                solution,
+                gridSettings
+            );
+            if (bytes === null) {
+                throw new Error("export failed");
+            }
+            expect(new Set(bytes)).toEqual(new Set([0, 1, 4]));
+
+            const imported = importNative(
+                parts,
+                requirements,
+                bytes,
+                gridSettings
+            );
+            if (imported === null) {
+                throw new Error("import failed");
+            }
+            expect(
+                exportNative(parts, requirements, imported, gridSettings)
+            ).toEqual(bytes);
+            expect(
+                importNative(parts, requirements, bytes, gridSettings)
+            ).toEqual(imported);
+            if (++n == 100) {
+                break;
+            }
+        }
+        expect(n).toBe(100);
+    });
+
+    test("round trips on a board with walls and out of bounds cells", () => {
+        const { parts, gridSettings, spinnableColors } = gameData(bn6);
+        const withWalls: GridSettings = {
+            ...gridSettings,
+            walls: [
+                [{ x: 3, y: 3 }, 0],
+                [{ x: 1, y: 4 }, 1],
+            ],
+        };
+        // SuprArmr twice and Shield.
+        const requirements = [req(0), req(7), req(0)];
+        let n = 0;
+        for (const solution of solve(
+            parts,
+            requirements,
+            withWalls,
+            spinnableColors
+        )) {
+            const bytes = exportNative(
+                parts,
+                requirements,
+                solution,
+                withWalls
+            );
+            if (bytes === null) {
+                throw new Error("export failed");
+            }
+            expect([...bytes]).toEqual(
+                placeAll(parts, requirements, solution, withWalls).map((v) =>
+                    v !== null ? requirements[v].partIndex + 1 : 0
+                )
+            );
+
+            const imported = importNative(
+                parts,
+                requirements,
+                bytes,
+                withWalls
+            );
+            if (imported === null) {
+                throw new Error("import failed");
+            }
+            expect(
+                exportNative(parts, requirements, imported, withWalls)
+            ).toEqual(bytes);
+            if (++n == 100) {
+                break;
+            }
+        }
+        expect(n).toBe(100);
+    });
+});
+
+describe("validateArgs", () => {
+    test("accepts wildcard parts without a color", () => {
+        expect(
+            validateArgs({
+                parts: [...parts, wildcardPart(gridSettings)],
+                requirements: [req(0), req(4, { wildcard: true })],
+                gridSettings,
+                spinnableColors,
+            })
+        ).toEqual([]);
+    });
+
+    test("reports grid settings problems along with the others", () => {
+        expect(
+            validateArgs({
+                parts: [...parts, part(true, 3, ["x"])],
+                requirements: [req(0), req(4)],
+                gridSettings: { ...gridSettings, commandLineRow: 4 },
+                spinnableColors,
+            })
+        ).toEqual([
+            { type: "colorOutOfRange", partIndex: 4 },
+            {
+                type: "invalidGridSettings",
+                error: { type: "commandLineRowOutOfRange" },
+            },
+        ]);
+    });
+});
+
+describe("maxCompressed", () => {
+    const { parts, gridSettings, spinnableColors } = gameData(bn3);
+    const countSolutions = (
+        requirements: Requirement[],
+        maxCompressed?: number
+    ) =>
+        count(
+            solve(parts, requirements, gridSettings, spinnableColors, {
+                maxCompressed,
+            })
+        );
+
+    test("doesn't count parts that can't be compressed", () => {
+        // SprArmr's masks are the same compressed and uncompressed.
+        expect(countSolutions([req(0)])).toBe(12);
+        expect(countSolutions([req(0)], 0)).toBe(12);
+    });
+
+    test("counts compressed placements", () => {
+        // Custom1 (yellow) has a smaller compressed mask.
+        const requirements = [req(10, { compressed: true })];
+        expect(countSolutions(requirements)).toBeGreaterThan(0);
+        expect(countSolutions(requirements, 0)).toBe(0);
+        expect(countSolutions(requirements, 1)).toBe(
+            countSolutions(requirements)
+        );
+    });
+});
+
+describe("scoreSolution", () => {
+    const { parts, gridSettings, spinnableColors } = gameData(bn3);
+    const weights = { bugs: 0, rotations: 0, compressed: 1, compactness: 0 };
+
+    test("only counts compressed placements of compressible parts", () => {
+        // SprArmr can't be compressed, Custom1 (yellow) can.
+        const requirements = [
+            req(0, { compressed: true, distinguishEqualCompression: true }),
+            req(10, { compressed: true }),
+        ];
+        const [solution] = solve(
+            parts,
+            requirements,
+            gridSettings,
+            spinnableColors
+        );
+        expect(solution.map((placement) => placement.compressed)).toEqual([
+            true,
+            true,
+        ]);
+        expect(
+            scoreSolution(parts, requirements, solution, gridSettings, weights)
+        ).toBe(1);
+    });
+});
+
+describe("pruneCommandLine", () => {
+    const bn6Data = gameData(bn6);
+    const parts = [...bn6Data.parts, wildcardPart(bn6Data.gridSettings)];
+    const { gridSettings, spinnableColors } = bn6Data;
+    const wildcard = parts.length - 1;
+
+    function solveCounting(requirements: Requirement[], prune: boolean) {
+        const stats = newStats();
+        const n = count(
+            solve(parts, requirements, gridSettings, spinnableColors, {
+                pruneCommandLine: prune,
+                stats,
+            })
+        );
+        return [n, stats.nodes];
+    }
+
+    // FstBarr, Reflect and ChpShufl.
+    const requirements = [6, 8, 13].map((i) =>
+        req(i, { onCommandLine: true })
+    );
+
+    test("finds the same solutions in fewer nodes", () => {
+        const [unpruned, unprunedNodes] = solveCounting(requirements, false);
+        const [pruned, prunedNodes] = solveCounting(requirements, true);
+        expect(unpruned).toBe(263);
+        expect(pruned).toBe(unpruned);
+        expect(prunedNodes).toBeLessThan(unprunedNodes);
+    });
+
+    test("doesn't require wildcards to be on the command line", () => {
+        const withWildcard = [
+            ...requirements,
+            req(wildcard, { wildcard: true, onCommandLine: true }),
+        ];
+        const [unpruned] = solveCounting(withWildcard, false);
+        const [pruned] = solveCounting(withWildcard, true);
+        expect(unpruned).toBe(7890);
+        expect(pruned).toBe(unpruned);
+    });
+});
+
+describe("SteppedSolver", () => {
+    const { parts, gridSettings, spinnableColors } = gameData(bn6);
+    // SuprArmr and Shield.
+    const requirements = [req(0), req(7)];
+
+    test("steps through every solution", () => {
+        const expected = [
+            ...solve(parts, requirements, gridSettings, spinnableColors),
+        ];
+        expect(expected).toHaveLength(2180);
+        for (const budget of [1, 100]) {
+            const solver = new SteppedSolver(
+                parts,
+                requirements,
+                gridSettings,
+                spinnableColors
+            );
+            const solutions: Solution[] = [];
+            let searching = 0;
+            for (;;) {
+                const r = solver.step(budget);
+                if (r.type == "done") {
+                    break;
+                }
+                if (r.type == "found") {
+                    solutions.push(r.solution);
+                } else {
+                    ++searching;
+                }
+            }
+            expect(solutions).toEqual(expected);
+            // With a budget of one, every node not ending in a solution is reported as searching.
+            if (budget == 1) {
+                expect(searching).toBeGreaterThan(0);
+            }
+        }
+    });
+});
+
+describe("maxNodes", () => {
+    const { parts, gridSettings, spinnableColors } = gameData(bn6);
+    // SuprArmr, Shield and SlipRunr.
+    const requirements = [req(0), req(7), req(24)];
+
+    test("stops the search and flags it in the stats", () => {
+        const stats = newStats();
+        expect(
+            count(
+                solve(parts, requirements, gridSettings, spinnableColors, {
+                    maxNodes: 50,
+                    stats,
+                })
+            )
+        ).toBe(36);
+        expect(stats.nodes).toBe(50);
+        expect(stats.nodeLimitHit).toBe(true);
+    });
+
+    test("doesn't flag searches finishing within the limit", () => {
+        const stats = newStats();
+        expect(
+            count(
+                solve(parts, requirements, gridSettings, spinnableColors, {
+                    stats,
+                })
+            )
+        ).toBe(83930);
+        expect(stats.nodeLimitHit).toBe(false);
+
+        const nodes = stats.nodes;
+        expect(
+            count(
+                solve(parts, requirements, gridSettings, spinnableColors, {
+                    maxNodes: nodes,
+                    stats,
+                })
+            )
+        ).toBe(83930);
+        expect(stats.nodeLimitHit).toBe(false);
+    });
+});
+
+describe("solveContaining", () => {
+    const { parts, gridSettings, spinnableColors } = gameData(bn6);
+    // SuprArmr and Shield.
+    const requirements = [req(0), req(7)];
+
+    test("yields exactly the solutions containing the placement", () => {
+        const all = [
+            ...solve(parts, requirements, gridSettings, spinnableColors),
+        ];
+        const placement = all[100][0];
+        const cells = JSON.stringify(
+            placementCells(parts[0], placement, gridSettings)
+        );
+        const expected = all.filter(
+            (solution) =>
+                solution[0].compressed == placement.compressed &&
+                JSON.stringify(
+                    placementCells(parts[0], solution[0], gridSettings)
+                ) == cells
+        );
+        expect(expected).toHaveLength(52);
+        expect(
+            sorted([
+                ...solveContaining(
+                    parts,
+                    requirements,
+                    gridSettings,
+                    spinnableColors,
+                    [[0, placement]]
+                ),
+            ])
+        ).toEqual(sorted(expected));
+    });
+});
+
+describe("subResolution", () => {
+    const subGridSettings: GridSettings = {
+        height: 1,
+        width: 2,
+        hasOob: false,
+        commandLineRow: 0,
+        subResolution: 2,
+    };
+    // Masks are given in sub-cells.
+    const maskGridSettings = { ...subGridSettings, height: 2, width: 4 };
+    const subParts = [
+        part(true, 0, ["xx", "xx"], maskGridSettings),
+        part(true, 1, ["x", "x"], maskGridSettings),
+    ];
+
+    test("places parts at sub-cell positions without overlapping", () => {
+        const solutions = [
+            ...solve(subParts, [req(0), req(1)], subGridSettings, [
+                false,
+                false,
+            ]),
+        ];
+        // The block can start at any of three sub-columns, leaving two for the sliver each time.
+        const xs = solutions
+            .map((solution) =>
+                solution.map((placement) => placement.loc.position.x).join()
+            )
+            .sort();
+        expect(xs).toEqual(["0,2", "0,3", "1,0", "1,3", "2,0", "2,1"]);
+        for (const solution of solutions) {
+            const cells = placeAll(
+                subParts,
+                [req(0), req(1)],
+                solution,
+                subGridSettings
+            );
+            expect(
+                [0, 1].map((i) => cells.filter((v) => v === i).length)
+            ).toEqual([4, 2]);
+        }
+    });
+});
+
+describe("borderForbidden", () => {
+    const { parts, gridSettings, spinnableColors } = gameData(bn6);
+    // SuprArmr and Shield.
+    const requirements = [req(0), req(7)];
+    const { height, width } = gridSettings;
+    const onBorder = (i: number) => {
+        const x = i % width;
+        const y = Math.floor(i / width);
+        return x == 0 || y == 0 || x == width - 1 || y == height - 1;
+    };
+
+    test("yields exactly the solutions not touching the border", () => {
+        const all = [
+            ...solve(parts, requirements, gridSettings, spinnableColors),
+        ];
+        const expected = all.filter((solution) =>
+            placeAll(parts, requirements, solution, gridSettings).every(
+                (v, i) => v === null || !onBorder(i)
+            )
+        );
+        expect(all).toHaveLength(2180);
+        expect(expected).toHaveLength(398);
+        expect(
+            sorted([
+                ...solve(
+                    parts,
+                    requirements,
+                    { ...gridSettings, borderForbidden: true },
+                    spinnableColors
+                ),
+            ])
+        ).toEqual(sorted(expected));
+    });
+});
+
+describe("distinguishEqualCompression", () => {
+    const { parts, gridSettings, spinnableColors } = gameData(bn3);
+
+    test("keeps both compression states of identical masks", () => {
+        // SprArmr's masks are the same compressed and uncompressed.
+        const requirements = [req(0)];
+        const distinguished = [req(0, { distinguishEqualCompression: true })];
+        expect(
+            searchEstimate(parts, requirements, gridSettings, spinnableColors)
+                .perRequirementCandidates
+        ).toEqual([12]);
+        expect(
+            searchEstimate(parts, distinguished, gridSettings, spinnableColors)
+                .perRequirementCandidates
+        ).toEqual([24]);
+
+        const solutions = [
+            ...solve(parts, distinguished, gridSettings, spinnableColors),
+        ];
+        expect(solutions).toHaveLength(24);
+        expect(
+            solutions.filter((solution) => solution[0].compressed)
+        ).toHaveLength(12);
+        expect(
+            count(solve(parts, requirements, gridSettings, spinnableColors))
+        ).toBe(12);
+    });
+});
+
+describe("forbidCornerAdjacency", () => {
+    const { parts, gridSettings, spinnableColors } = gameData(bn6);
+    // SuprArmr and Shield.
+    const requirements = [req(0), req(7)];
+    const { height, width } = gridSettings;
+    const nextToCorner = (i: number) => {
+        const x = i % width;
+        const y = Math.floor(i / width);
+        return (
+            ((y == 0 || y == height - 1) && (x == 1 || x == width - 2)) ||
+            ((x == 0 || x == width - 1) && (y == 1 || y == height - 2))
+        );
+    };
+
+    test("yields exactly the solutions overhanging no corner", () => {
+        const all = [
+            ...solve(parts, requirements, gridSettings, spinnableColors),
+        ];
+        const expected = all.filter((solution) =>
+            placeAll(parts, requirements, solution, gridSettings).every(
+                (v, i) => v === null || !nextToCorner(i)
+            )
+        );
+        // Without the setting, parts may overhang into the corner rows and columns.
+        expect(all).toHaveLength(2180);
+        expect(expected).toHaveLength(1456);
+        expect(
+            sorted([
+                ...solve(
+                    parts,
+                    requirements,
+                    { ...gridSettings, forbidCornerAdjacency: true },
+                    spinnableColors
+                ),
+            ])
+        ).toEqual(sorted(expected));
+    });
+});
+
+describe("Solver", () => {
+    const { parts, gridSettings, spinnableColors } = gameData(bn6);
+
+    test("gives the same solutions as solve across repeated solves", () => {
+        const solver = new Solver(parts, gridSettings, spinnableColors);
+        // The second set shares SuprArmr's candidates with the first.
+        for (const requirements of [
+            [req(0), req(7)],
+            [req(0), req(24)],
+            [req(0), req(7)],
+        ]) {
+            const expected = [
+                ...solve(parts, requirements, gridSettings, spinnableColors),
+            ];
+            expect(expected.length).toBeGreaterThan(0);
+            expect([...solver.solve(requirements)]).toEqual(expected);
+        }
+        expect(solver.candidateCache.size).toBe(3);
+    });
+});
+
+describe("compressedColor", () => {
+    const lineGridSettings: GridSettings = {
+        height: 1,
+        width: 2,
+        hasOob: false,
+        commandLineRow: 0,
+    };
+    const lineParts = [
+        part(true, 0, ["x"], lineGridSettings),
+        { ...part(true, 1, ["x"], lineGridSettings), compressedColor: 0 },
+    ];
+
+    function solveBugFree(compressed: boolean) {
+        return [
+            ...solve(
+                lineParts,
+                [
+                    req(0, { maxBugLevel: 0 }),
+                    req(1, { compressed, maxBugLevel: 0 }),
+                ],
+                lineGridSettings,
+                [false, false]
+            ),
+        ];
+    }
+
+    test("bugs neighbors of the compressed color", () => {
+        expect(solveBugFree(false)).toHaveLength(2);
+        expect(solveBugFree(true)).toHaveLength(0);
+
+        const requirements = [req(0), req(1, { compressed: true })];
+        const solutions = [
+            ...solve(lineParts, requirements, lineGridSettings, [false, false]),
+        ];
+        expect(solutions).toHaveLength(2);
+        for (const solution of solutions) {
+            expect(
+                placementBugReport(
+                    lineParts,
+                    requirements,
+                    solution,
+                    lineGridSettings
+                ).map((b) => b.touchingSameColor)
+            ).toEqual([true, true]);
+        }
+    });
+});
+
+describe("disableRotations", () => {
+    const { parts, gridSettings } = gameData(bn6);
+    // SuprArmr and Shield.
+    const requirements = [req(0), req(7)];
+    const spinnable = bn6.colors.map(() => true);
+    const unspinnable = bn6.colors.map(() => false);
+
+    test("places spinnable parts as if they weren't", () => {
+        expect(
+            count(solve(parts, requirements, gridSettings, spinnable))
+        ).toBe(17392);
+        const solutions = [
+            ...solve(
+                parts,
+                requirements,
+                { ...gridSettings, disableRotations: true },
+                spinnable
+            ),
+        ];
+        expect(
+            solutions.every((solution) =>
+                solution.every((placement) => placement.loc.rotation == 0)
+            )
+        ).toBe(true);
+        expect(solutions).toEqual([
+            ...solve(parts, requirements, gridSettings, unspinnable),
+        ]);
+    });
+});
+
+describe("anchor", () => {
+    const { parts, gridSettings, spinnableColors } = gameData(bn6);
+
+    test("yields exactly the solutions with the part at the anchor", () => {
+        // SuprArmr and Shield.
+        const all = [
+            ...solve(parts, [req(0), req(7)], gridSettings, spinnableColors),
+        ];
+        const anchor = { x: -2, y: -2 };
+        const expected = all.filter(
+            (solution) =>
+                solution[0].loc.position.x == anchor.x &&
+                solution[0].loc.position.y == anchor.y
+        );
+        expect(expected).toHaveLength(55);
+        expect(
+            sorted([
+                ...solve(
+                    parts,
+                    [req(0, { anchor }), req(7)],
+                    gridSettings,
+                    spinnableColors
+                ),
+            ])
+        ).toEqual(sorted(expected));
+    });
+});
+
+describe("commandLineMatrix", () => {
+    const { parts, gridSettings, spinnableColors } = gameData(bn6);
+    // SuprArmr is solid, HP+50 isn't.
+    const requirements = [req(0), req(46)];
+    const all = [...solve(parts, requirements, gridSettings, spinnableColors)];
+
+    function onCommandLine(solution: Solution): boolean[] {
+        const cells = placeAll(parts, requirements, solution, gridSettings);
+        return requirements.map((_, i) =>
+            cells.some(
+                (v, j) =>
+                    v === i &&
+                    Math.floor(j / gridSettings.width) ==
+                        gridSettings.commandLineRow
+            )
+        );
+    }
+
+    test("defaults to solid parts wanting the command line and non-solid parts not", () => {
+        expect(all).toHaveLength(1044);
+        for (const gs of [
+            gridSettings,
+            { ...gridSettings, commandLineMatrix: DEFAULT_COMMAND_LINE_MATRIX },
+        ]) {
+            for (const solution of all) {
+                const on = onCommandLine(solution);
+                expect(
+                    placementBugReport(parts, requirements, solution, gs).map(
+                        (b) => b.commandLineBugged
+                    )
+                ).toEqual(
+                    requirements.map(
+                        (r, i) => parts[r.partIndex].isSolid != on[i]
+                    )
+                );
+            }
+        }
+    });
+
+    test("can have both kinds of parts want the command line", () => {
+        const bugFree = requirements.map((r) =>
+            req(r.partIndex, { maxBugLevel: 0 })
+        );
+        const solutions = [
+            ...solve(
+                parts,
+                bugFree,
+                {
+                    ...gridSettings,
+                    commandLineMatrix: {
+                        solidOn: false,
+                        solidOff: true,
+                        nonSolidOn: false,
+                        nonSolidOff: true,
+                    },
+                },
+                spinnableColors
+            ),
+        ];
+        expect(solutions).toHaveLength(42);
+        for (const solution of solutions) {
+            expect(onCommandLine(solution)).toEqual([true, true]);
+        }
+    });
+});
+
+describe("walls", () => {
+    const { parts, gridSettings, spinnableColors } = gameData(bn6);
+    // SuprArmr and Shield.
+    const requirements = [req(0), req(7)];
+
+    test("yields exactly the solutions not covering a wall", () => {
+        const walls: [Position, number][] = [
+            [{ x: 3, y: 3 }, 0],
+            [{ x: 1, y: 4 }, 1],
+        ];
+        const wallCells = walls.map(([{ x, y }]) => y * gridSettings.width + x);
+        const all = [
+            ...solve(parts, requirements, gridSettings, spinnableColors),
+        ];
+        const expected = all.filter((solution) => {
+            const cells = placeAll(parts, requirements, solution, gridSettings);
+            return wallCells.every((i) => cells[i] === null);
+        });
+        expect(all).toHaveLength(2180);
+        expect(expected).toHaveLength(1272);
+        expect(
+            sorted([
+                ...solve(
+                    parts,
+                    requirements,
+                    { ...gridSettings, walls },
+                    spinnableColors
+                ),
+            ])
+        ).toEqual(sorted(expected));
+    });
+});
+
+describe("searchEstimate", () => {
+    test("counts the candidates of each requirement", () => {
+        const { parts, gridSettings, spinnableColors } = gameData(bn3);
+        // SprArmr can be placed in 12 places.
+        const estimate = searchEstimate(
+            parts,
+            [req(0), req(0)],
+            gridSettings,
+            spinnableColors
+        );
+        expect(estimate.perRequirementCandidates).toEqual([12, 12]);
+        expect(estimate.log10ProductUpperBound).toBeCloseTo(Math.log10(144));
+    });
+
+    test("bounds searches too large for the product to be exact", () => {
+        const { parts, gridSettings } = gameData(bn6);
+        const estimate = searchEstimate(
+            parts,
+            parts.slice(0, 20).map((_, i) => req(i)),
+            gridSettings,
+            bn6.colors.map(() => true)
+        );
+        // Past 2 ** 53, where numbers can't hold the product exactly.
+        expect(estimate.log10ProductUpperBound).toBeGreaterThan(16);
+        expect(estimate.log10ProductUpperBound).toBeCloseTo(
+            estimate.perRequirementCandidates.reduce(
+                (sum, n) => sum + Math.log10(n),
+                0
+            )
+        );
+    });
+});
+
+describe("sizeHint", () => {
+    const { parts, gridSettings, spinnableColors } = gameData(bn6);
+
+    test("bounds the number of solutions", () => {
+        // SuprArmr and Shield.
+        const requirements = [req(0), req(7)];
+        const [lower, upper] = solve(
+            parts,
+            requirements,
+            gridSettings,
+            spinnableColors
+        ).sizeHint();
+        expect(lower).toBe(0);
+        expect(upper).toBe(
+            searchEstimate(
+                parts,
+                requirements,
+                gridSettings,
+                spinnableColors
+            ).perRequirementCandidates.reduce((product, n) => product * n, 1)
+        );
+        expect(upper).not.toBeLessThan(
+            count(solve(parts, requirements, gridSettings, spinnableColors))
+        );
+    });
+
+    test("throws the solve error for invalid requirements", () => {
+        const it = solve(parts, [req(0), req(parts.length)], gridSettings, []);
+        let error: unknown = null;
+        try {
+            it.sizeHint();
+        } catch (e) {
+            error = e;
+        }
+        expect(error).toEqual({
+            type: "partIndexOutOfRange",
+            requirementIndex: 1,
+        });
+    });
+});
+
+describe("exportCandidates", () => {
+    test("exports the cells each placement covers", () => {
+        const { parts, gridSettings, spinnableColors } = gameData(bn6);
+        const withWalls: GridSettings = {
+            ...gridSettings,
+            walls: [
+                [{ x: 3, y: 3 }, 0],
+                [{ x: 1, y: 4 }, 1],
+            ],
+        };
+        // With SuprArmr alone, each candidate is a solution.
+        const requirements = [req(0)];
+        const args = {
+            parts,
+            requirements,
+            gridSettings: withWalls,
+            spinnableColors,
+        };
+        const [candidates] = exportCandidates(args);
+        const solutions = [
+            ...solve(parts, requirements, withWalls, spinnableColors),
+        ];
+        expect(candidates).toHaveLength(solutions.length);
+        expect(
+            sorted(candidates.map(({ placement }) => [placement]))
+        ).toEqual(sorted(solutions));
+        for (const { placement, cells } of candidates) {
+            const occupied = placeAll(
+                parts,
+                requirements,
+                [placement],
+                withWalls
+            )
+                .map((req, i) => (req === null ? null : i))
+                .filter((i) => i !== null);
+            expect(cells).toEqual(occupied);
+        }
+        expect(exportCandidates(args)).toEqual([candidates]);
+    });
+});
+
+describe("wildcard colors", () => {
+    const bn6Data = gameData(bn6);
+    const parts = [...bn6Data.parts, wildcardPart(bn6Data.gridSettings)];
+    const { gridSettings, spinnableColors } = bn6Data;
+    // SuprArmr and a wildcard touching it.
+    const requirements = [req(0), req(parts.length - 1, { wildcard: true })];
+    const touching = [
+        ...solve(parts, requirements, gridSettings, spinnableColors),
+    ].find(
+        (solution) =>
+            adjacencyGraph(parts, requirements, solution, gridSettings)
+                .length > 0
+    ) as Solution;
+
+    test("aren't counted by colorHistogram", () => {
+        expect(colorHistogram(parts, requirements, touching)).toEqual([
+            [parts[0].color, 1],
+        ]);
+    });
+
+    test("aren't penalized by scoreSolution", () => {
+        expect(
+            scoreSolution(parts, requirements, touching, gridSettings, {
+                bugs: 0,
+                rotations: 0,
+                compressed: 0,
+                compactness: 0,
+                colorTouchPenalties: [[-1, parts[0].color, 5]],
+            })
+        ).toBe(0);
+    });
+});
+
+describe("dedupRequirements", () => {
+    const { parts, gridSettings, spinnableColors } = gameData(bn6);
+    // SuprArmr three times and Shield.
+    const requirements = [req(0), req(0), req(0), req(7)];
+
+    test("collapses identical requirements", () => {
+        const [unique, mapping] = dedupRequirements(requirements);
+        expect(unique).toEqual([req(0), req(7)]);
+        expect(mapping).toEqual([0, 0, 0, 1]);
+        expect(mapping.filter((i) => i == 0)).toHaveLength(3);
+        expect(expandDeduped(unique, mapping)).toEqual(requirements);
+    });
+
+    test("expands values found for the unique requirements", () => {
+        const [unique, mapping] = dedupRequirements(requirements);
+        expect(
+            expandDeduped(
+                searchEstimate(parts, unique, gridSettings, spinnableColors)
+                    .perRequirementCandidates,
+                mapping
+            )
+        ).toEqual(
+            searchEstimate(parts, requirements, gridSettings, spinnableColors)
+                .perRequirementCandidates
+        );
+    });
+});
+
+describe("validatePart", () => {
+    test("accepts a part compressing to a subset of itself", () => {
+        const valid = {
+            ...part(true, 0, ["xxx", ".x."]),
+            compressedMask: mask(["xxx"]),
+        };
+        expect(validatePart(valid)).toBeNull();
+        expect(validatePart(valid, true)).toBeNull();
+    });
+
+    test("rejects a compressed mask larger than the uncompressed one", () => {
+        expect(
+            validatePart({
+                ...part(true, 0, ["xx"]),
+                compressedMask: mask(["xxx"]),
+            })
+        ).toEqual({
+            type: "compressedLarger",
+            compressedCells: 3,
+            uncompressedCells: 2,
+        });
+    });
+
+    test("rejects compressed cells the uncompressed mask lacks when strict", () => {
+        // The same number of cells, with the compressed one sticking out on the right.
+        const reshaped = {
+            ...part(true, 0, ["xx", "x."]),
+            compressedMask: mask(["xxx"]),
+        };
+        expect(validatePart(reshaped)).toBeNull();
+        expect(validatePart(reshaped, true)).toEqual({
+            type: "compressedNotSubset",
+            position: { x: 2, y: 0 },
+        });
+    });
+
+    test("rejects masks of different sizes", () => {
+        expect(
+            validatePart({
+                ...part(true, 0, ["xx"]),
+                compressedMask: array2d.full(true, 1, 1),
+            })
+        ).toEqual({ type: "dimensionMismatch" });
+    });
+
+    test("rejects empty masks", () => {
+        expect(
+            validatePart({ ...part(true, 0, ["x"]), compressedMask: mask([]) })
+        ).toEqual({ type: "emptyMask", compressed: true });
+    });
+
+    test("accepts the game's parts unless strict", () => {
+        const { parts } = gameData(bn6);
+        expect(validateParts(parts)).toEqual([]);
+        expect(validateParts(parts, true).length).toBeGreaterThan(0);
+    });
+});
+
+describe("maskFromRomBytes", () => {
+    test("decodes SuprArmr", () => {
+        const { parts } = gameData(bn6);
+        // SuprArmr compresses into a T.
+        const bytes = [0x00, 0x00, 0x10, 0x38, 0x00, 0x00, 0x00];
+        expect(maskFromRomBytes(bytes, 7, 7)).toEqual(parts[0].compressedMask);
+    });
+
+    test("pads each row to whole bytes", () => {
+        expect(maskFromRomBytes([0x80, 0x80, 0x00, 0x80], 2, 9)).toEqual(
+            mask(["x.......x", "........x"], {
+                ...gridSettings,
+                height: 2,
+                width: 9,
+            })
+        );
+    });
+
+    test("rejects the wrong number of bytes", () => {
+        expect(maskFromRomBytes([0x00, 0x00], 3, 7)).toBeNull();
+    });
+});
+
+describe("solutionsEquivalent", () => {
+    // The two cell non-solid part alone.
+    const requirements = [req(2)];
+    const [solution] = solve(
+        parts,
+        requirements,
+        gridSettings,
+        spinnableColors
+    );
+
+    test("matches a solution rotated by 180 degrees on a symmetric board", () => {
+        const symmetric: GridSettings = {
+            ...gridSettings,
+            commandLineRules: new Array(gridSettings.height).fill(
+                CommandLineRule.None
+            ),
+        };
+        const cells = placeAll(parts, requirements, solution, symmetric);
+        const rotated = [...cells].reverse();
+        expect(rotated).not.toEqual(cells);
+        expect(solutionsEquivalent(cells, rotated, symmetric)).toBe(true);
+    });
+
+    test("doesn't match across the command line", () => {
+        // The command line is on the second row, so turning the board around moves it.
+        const cells = placeAll(parts, requirements, solution, gridSettings);
+        expect(
+            solutionsEquivalent(cells, [...cells].reverse(), gridSettings)
+        ).toBe(false);
+    });
+
+    test("doesn't match different solutions", () => {
+        const cells = placeAll(parts, requirements, solution, gridSettings);
+        expect(
+            solutionsEquivalent(
+                cells,
+                new Array<number | null>(cells.length).fill(null),
+                gridSettings
+            )
+        ).toBe(false);
+    });
+});
+
+describe("placementReasons", () => {
+    // The two cell non-solid part, required to be on the command line.
+    const requirement = req(2, { onCommandLine: true });
+    const reasons = placementReasons(
+        parts[2],
+        gridSettings,
+        requirement.constraint,
+        spinnableColors[2]
+    );
+
+    test("rejects placements off the command line", () => {
+        expect(reasons).toContainEqual([
+            {
+                loc: { position: { x: 0, y: 0 }, rotation: 0 },
+                compressed: false,
+            },
+            { type: "rejected", rejection: "onCommandLineMismatch" },
+        ]);
+        for (const [placement, reason] of reasons) {
+            const onCommandLine = placementCells(
+                parts[2],
+                placement,
+                gridSettings
+            ).some(({ y }) => y == gridSettings.commandLineRow);
+            if (reason.type == "accepted") {
+                expect(onCommandLine).toBe(true);
+            } else if (reason.rejection == "onCommandLineMismatch") {
+                expect(onCommandLine).toBe(false);
+            }
+        }
+    });
+
+    test("accepts the candidates", () => {
+        const [candidates] = searchEstimate(
+            parts,
+            [requirement],
+            gridSettings,
+            spinnableColors
+        ).perRequirementCandidates;
+        expect(candidates).toBeGreaterThan(0);
+        expect(
+            reasons.filter(([, reason]) => reason.type == "accepted")
+        ).toHaveLength(candidates);
+    });
+});
@@ -3,6 +3,8 @@ import * as array2d from "./array2d";
 export interface Part {
     isSolid: boolean;
     color: number;
+    // The color the part takes on when compressed, if different.
+    compressedColor?: number;
     compressedMask: array2d.Array2D<boolean>;
     uncompressedMask: array2d.Array2D<boolean>;
 }
@@ -12,6 +14,14 @@ export interface Constraint {
     onCommandLine: boolean | null;
     minBugLevel: number;
     maxBugLevel: number;
+    // Inclusive top-left and bottom-right corners the part must stay within.
+    region?: [Position, Position];
+    // Keeps both compressed and uncompressed placements even if the masks are identical, as they are distinct states in game.
+    distinguishEqualCompression?: boolean;
+    // Fills any empty cell, ignoring the bug rules and not bugging parts it touches. The part should be a single cell, as made by wildcardPart.
+    wildcard?: boolean;
+    // The position the rotated mask's top-left corner must be placed at, as in Location.position.
+    anchor?: Position;
 }
 
 export interface Requirement {
@@ -19,11 +29,48 @@ export interface Requirement {
     constraint: Constraint;
 }
 
+// A requirement as hand-authored, referring to its part by either index or name.
+export interface NamedRequirement {
+    partIndex?: number;
+    partName?: string;
+    constraint: Constraint;
+}
+
+export enum CommandLineRule {
+    None,
+    // Solid parts must touch a row with this rule, and non-solid parts must not.
+    SolidPreferred,
+    // Non-solid parts must touch a row with this rule, and solid parts must not.
+    NonSolidPreferred,
+}
+
 export interface GridSettings {
     height: number;
     width: number;
     hasOob: boolean;
     commandLineRow: number;
+    // The rule for each row, overriding commandLineRow. Rows requiring solid parts are the ones considered to be on the command line.
+    commandLineRules?: CommandLineRule[];
+    // Which placements the command line bugs. Defaults to DEFAULT_COMMAND_LINE_MATRIX.
+    commandLineMatrix?: CommandLineMatrix;
+    // Splits each cell into subResolution x subResolution sub-cells, at which part masks and positions are given. The command line row and the out of bounds border are still given in whole cells.
+    subResolution?: number;
+    // Cells pre-occupied by obstacles, each with its own wall id.
+    walls?: [Position, number][];
+    // Forbids the entire outer ring of cells, not just the corners.
+    borderForbidden?: boolean;
+    // With hasOob, also forbids the cells next to the corners, so parts can't overhang into the corner rows and columns.
+    forbidCornerAdjacency?: boolean;
+    // The rotations spinnable parts may be placed in. Defaults to quarter turns.
+    rotationGroup?: RotationGroup;
+    // Places every part unrotated, even those of spinnable colors.
+    disableRotations?: boolean;
+}
+
+export enum RotationGroup {
+    Quarter,
+    // Needs masks that can be turned diagonally, so isn't supported yet.
+    Eighth,
 }
 
 export interface Position {
@@ -43,19 +90,168 @@ export interface Placement {
 
 export type Solution = Placement[];
 
+// Number of distinct 90 degree rotations a part can be placed in.
+export const ROTATIONS = 4;
+
+// Returns the number of rotations the grid allows, or null if its rotation group isn't supported.
+function gridRotations(gridSettings: GridSettings): number | null {
+    switch (
+        gridSettings.rotationGroup === undefined
+            ? RotationGroup.Quarter
+            : gridSettings.rotationGroup
+    ) {
+        case RotationGroup.Quarter:
+            return ROTATIONS;
+        default:
+            return null;
+    }
+}
+
+export interface SolveStats {
+    nodes: number;
+    nodeLimitHit: boolean;
+    // The most requirements that were placed at once.
+    depth: number;
+    // The requirement that couldn't be placed at that depth, or null if all of them were.
+    deepestRequirement: number | null;
+    // How many of the first requirement's candidates have been tried, out of how many, for showing progress through an exhaustive solve.
+    firstLevelTried: number;
+    firstLevelTotal: number;
+}
+
+export interface SolveOptions {
+    // Prune branches where the empty space touching the command line can no longer fit the remaining parts required to be on it. Wildcards are never required to be on it.
+    pruneCommandLine?: boolean;
+    // Stop searching after trying this many candidate placements.
+    maxNodes?: number;
+    // Updated as the search progresses.
+    stats?: SolveStats;
+    // Only yield solutions where these requirements are placed covering the same cells as the given placements.
+    containing?: [number, Placement][];
+    // Compressing costs resources in game, so only allow up to this many compressed placements. Placements of parts that aren't compressible don't count.
+    maxCompressed?: number;
+    // Only allow up to this many bugged placements.
+    maxBugs?: number;
+    // Only yield solutions covering every cell of the command line.
+    fillCommandLine?: boolean;
+    // Pairs of requirements as [a, b, k], whose placements must have no cells closer than k apart, counting steps along rows and columns.
+    minDistances?: [number, number, number][];
+}
+
 enum Cell {
     Forbidden = -2,
     Empty = -1,
 }
 
-function arrayCountNumber(arr: number[], p: number): number {
-    let n = 0;
-    for (const v of arr) {
-        if (v === p) {
-            n += 1;
+// Walls are stored below Cell.Forbidden, so they are never mistaken for placed requirements.
+function wallCell(wallId: number): number {
+    return Cell.Forbidden - 1 - wallId;
+}
+
+function cellWallId(cell: number): number {
+    return Cell.Forbidden - 1 - cell;
+}
+
+function commandLineRulesForGridSettings(
+    gridSettings: GridSettings
+): CommandLineRule[] {
+    if (gridSettings.commandLineRules !== undefined) {
+        return gridSettings.commandLineRules;
+    }
+    const rules = new Array<CommandLineRule>(gridSettings.height).fill(
+        CommandLineRule.None
+    );
+    rules[gridSettings.commandLineRow] = CommandLineRule.SolidPreferred;
+    return rules;
+}
+
+// Whether solid and non-solid parts are bugged when on or off the command line.
+export interface CommandLineMatrix {
+    solidOn: boolean;
+    solidOff: boolean;
+    nonSolidOn: boolean;
+    nonSolidOff: boolean;
+}
+
+// Solid parts want to be on the command line and non-solid parts off it.
+export const DEFAULT_COMMAND_LINE_MATRIX: CommandLineMatrix = {
+    solidOn: false,
+    solidOff: true,
+    nonSolidOn: true,
+    nonSolidOff: false,
+};
+
+// The matrix only covers rows preferring solid parts, and only applies when the grid has some. Regardless of it, when the grid has rows preferring non-solid parts, solid parts touching them are bugged and so are non-solid parts not touching any.
+function isCommandLineBugged(
+    grid: Grid,
+    isSolid: boolean,
+    onCommandLine: boolean,
+    onNonSolidCommandLine: boolean
+) {
+    const matrix = grid.commandLineMatrix;
+    return isSolid
+        ? (grid.hasSolidCommandLine &&
+              (onCommandLine ? matrix.solidOn : matrix.solidOff)) ||
+              (grid.hasNonSolidCommandLine && onNonSolidCommandLine)
+        : (grid.hasSolidCommandLine &&
+              (onCommandLine ? matrix.nonSolidOn : matrix.nonSolidOff)) ||
+              (grid.hasNonSolidCommandLine && !onNonSolidCommandLine);
+}
+
+// Returns the number of rows and columns of sub-cells the grid has.
+function gridDimensions(gridSettings: GridSettings): [number, number] {
+    const subResolution = gridSettings.subResolution || 1;
+    return [
+        gridSettings.height * subResolution,
+        gridSettings.width * subResolution,
+    ];
+}
+
+// Returns the indices of the cells of a grid with the given dimensions the mask covers when placed at the position, in row-major order. Cells falling outside of the grid are clipped.
+function maskCellIndexes(
+    mask: array2d.Array2D<boolean>,
+    pos: Position,
+    nrows: number,
+    ncols: number
+): number[] {
+    const cells: number[] = [];
+
+    let srcTop = 0;
+    let dstTop = 0;
+    if (pos.y < 0) {
+        srcTop = -pos.y;
+    } else {
+        dstTop = pos.y;
+    }
+
+    let srcLeft = 0;
+    let dstLeft = 0;
+    if (pos.x < 0) {
+        srcLeft = -pos.x;
+    } else {
+        dstLeft = pos.x;
+    }
+
+    for (let y = 0; y < mask.nrows - srcTop; ++y) {
+        for (let x = 0; x < mask.ncols - srcLeft; ++x) {
+            const srcX = x + srcLeft;
+            const srcY = y + srcTop;
+            const dstX = x + dstLeft;
+            const dstY = y + dstTop;
+
+            if (!mask[srcY * mask.ncols + srcX]) {
+                continue;
+            }
+
+            if (dstX >= ncols || dstY >= nrows) {
+                continue;
+            }
+
+            cells.push(dstY * ncols + dstX);
         }
     }
-    return n;
+
+    return cells;
 }
 
 function arrayCountTrue(arr: boolean[]): number {
@@ -86,6 +282,13 @@ function arrayBooleanToNumber(arr: boolean[]): number[] {
 }
 
 function trim(arr2d: array2d.Array2D<boolean>): array2d.Array2D<boolean> {
+    return trimWithOffset(arr2d)[0];
+}
+
+// Trims empty rows and columns off the mask, also returning how many columns and rows were removed from the left and top.
+export function trimWithOffset(
+    arr2d: array2d.Array2D<boolean>
+): [array2d.Array2D<boolean>, Position] {
     let left = 0;
     for (; left < arr2d.ncols; ++left) {
         if (arrayAny(array2d.col(arr2d, left))) {
@@ -119,43 +322,102 @@ function trim(arr2d: array2d.Array2D<boolean>): array2d.Array2D<boolean> {
     const nrows = bottom - top;
     const ncols = right - left;
 
-    return array2d.subarray(arr2d, top, left, nrows, ncols);
+    return [
+        array2d.subarray(arr2d, top, left, nrows, ncols),
+        { x: left, y: top },
+    ];
 }
 
 class Grid {
     hasOob: boolean;
-    commandLineRow: number;
+    commandLineRules: CommandLineRule[];
+    commandLineMatrix: CommandLineMatrix;
+    hasSolidCommandLine: boolean;
+    hasNonSolidCommandLine: boolean;
+    subResolution: number;
     cells: array2d.Array2D<number>;
 
     constructor(settings: GridSettings) {
         this.hasOob = settings.hasOob;
-        this.commandLineRow = settings.commandLineRow;
-        this.cells = array2d.full<number>(
-            Cell.Empty,
-            settings.height,
-            settings.width
+        this.commandLineRules = commandLineRulesForGridSettings(settings);
+        this.commandLineMatrix =
+            settings.commandLineMatrix || DEFAULT_COMMAND_LINE_MATRIX;
+        this.hasSolidCommandLine = this.commandLineRules.includes(
+            CommandLineRule.SolidPreferred
+        );
+        this.hasNonSolidCommandLine = this.commandLineRules.includes(
+            CommandLineRule.NonSolidPreferred
+        );
+        this.subResolution = settings.subResolution || 1;
+        const [height, width] = gridDimensions(settings);
+        this.cells = array2d.full<number>(Cell.Empty, height, width);
+        if (this.hasOob || settings.borderForbidden) {
+            for (let y = 0; y < height; ++y) {
+                for (let x = 0; x < width; ++x) {
+                    const onRowBorder =
+                        y < this.subResolution ||
+                        y >= height - this.subResolution;
+                    const onColBorder =
+                        x < this.subResolution ||
+                        x >= width - this.subResolution;
+                    if (
+                        settings.borderForbidden
+                            ? onRowBorder || onColBorder
+                            : onRowBorder && onColBorder
+                    ) {
+                        this.cells[y * width + x] = Cell.Forbidden;
+                    }
+                }
+            }
+        }
+        if (this.hasOob && settings.forbidCornerAdjacency) {
+            for (let y = 0; y < height; ++y) {
+                for (let x = 0; x < width; ++x) {
+                    const row = Math.floor(y / this.subResolution);
+                    const col = Math.floor(x / this.subResolution);
+                    const onRowBorder = row == 0 || row == settings.height - 1;
+                    const onColBorder = col == 0 || col == settings.width - 1;
+                    if (
+                        (onRowBorder &&
+                            (col == 1 || col == settings.width - 2)) ||
+                        (onColBorder &&
+                            (row == 1 || row == settings.height - 2))
+                    ) {
+                        this.cells[y * width + x] = Cell.Forbidden;
+                    }
+                }
+            }
+        }
+        for (const [{ x, y }, wallId] of settings.walls || []) {
+            if (x < 0 || x >= width || y < 0 || y >= height) {
+                continue;
+            }
+            this.cells[y * width + x] = wallCell(wallId);
+        }
+    }
+
+    isOutOfBounds(x: number, y: number) {
+        return (
+            this.hasOob &&
+            (x < this.subResolution ||
+                y < this.subResolution ||
+                x >= this.cells.ncols - this.subResolution ||
+                y >= this.cells.nrows - this.subResolution)
+        );
+    }
+
+    isOnCommandLine(y: number) {
+        return (
+            this.commandLineRules[Math.floor(y / this.subResolution)] ===
+            CommandLineRule.SolidPreferred
+        );
+    }
+
+    isOnNonSolidCommandLine(y: number) {
+        return (
+            this.commandLineRules[Math.floor(y / this.subResolution)] ===
+            CommandLineRule.NonSolidPreferred
         );
-        if (this.hasOob) {
-            this.cells[0 * settings.width + 0] = Cell.Forbidden;
-            this.cells[0 * settings.width + (settings.width - 1)] =
-                Cell.Forbidden;
-            this.cells[
-                (settings.height - 1) * settings.width + (settings.width - 1)
-            ] = Cell.Forbidden;
-            this.cells[(settings.height - 1) * settings.width + 0] =
-                Cell.Forbidden;
-        }
-    }
-
-    clone(): Grid {
-        const grid = new Grid({
-            hasOob: this.hasOob,
-            commandLineRow: this.commandLineRow,
-            width: 0,
-            height: 0,
-        });
-        grid.cells = array2d.copy(this.cells);
-        return grid;
     }
 
     canPlace(mask: array2d.Array2D<boolean>, pos: Position) {
@@ -218,46 +480,74 @@ class Grid {
         return true;
     }
 
+    // Returns the indices of the filled cells, which can be passed to undo to revert the placement.
     placeNoCheck(
         mask: array2d.Array2D<boolean>,
         pos: Position,
         reqIdx: number
-    ) {
-        let srcTop = 0;
-        let dstTop = 0;
-        if (pos.y < 0) {
-            srcTop = -pos.y;
-        } else {
-            dstTop = pos.y;
+    ): number[] {
+        const filled = maskCellIndexes(
+            mask,
+            pos,
+            this.cells.nrows,
+            this.cells.ncols
+        );
+        for (const i of filled) {
+            this.cells[i] = reqIdx;
         }
+        return filled;
+    }
 
-        let srcLeft = 0;
-        let dstLeft = 0;
-        if (pos.x < 0) {
-            srcLeft = -pos.x;
-        } else {
-            dstLeft = pos.x;
+    undo(filled: number[]) {
+        for (const i of filled) {
+            this.cells[i] = Cell.Empty;
         }
+    }
 
-        // Actually do the placement...
-        for (let y = 0; y < mask.nrows - srcTop; ++y) {
-            for (let x = 0; x < mask.ncols - srcLeft; ++x) {
-                const srcX = x + srcLeft;
-                const srcY = y + srcTop;
-                const dstX = x + dstLeft;
-                const dstY = y + dstTop;
-
-                if (!mask[srcY * mask.ncols + srcX]) {
-                    continue;
-                }
+    // Returns the orthogonally connected regions of empty cells, as lists of cell indices.
+    emptyRegions(): number[][] {
+        const regions: number[][] = [];
+        const seen = new Array<boolean>(this.cells.length).fill(false);
+        for (let i = 0; i < this.cells.length; ++i) {
+            if (seen[i] || this.cells[i] != Cell.Empty) {
+                continue;
+            }
 
-                if (dstX >= this.cells.ncols || dstY >= this.cells.nrows) {
-                    continue;
+            const region: number[] = [];
+            const stack = [i];
+            seen[i] = true;
+            while (stack.length > 0) {
+                const j = stack.pop() as number;
+                region.push(j);
+
+                const x = j % this.cells.ncols;
+                const y = Math.floor(j / this.cells.ncols);
+                for (const [x2, y2] of [
+                    [x - 1, y],
+                    [x + 1, y],
+                    [x, y - 1],
+                    [x, y + 1],
+                ]) {
+                    if (
+                        x2 < 0 ||
+                        x2 >= this.cells.ncols ||
+                        y2 < 0 ||
+                        y2 >= this.cells.nrows
+                    ) {
+                        continue;
+                    }
+
+                    const k = y2 * this.cells.ncols + x2;
+                    if (seen[k] || this.cells[k] != Cell.Empty) {
+                        continue;
+                    }
+                    seen[k] = true;
+                    stack.push(k);
                 }
-
-                this.cells[dstY * this.cells.ncols + dstX] = reqIdx;
             }
+            regions.push(region);
         }
+        return regions;
     }
 }
 
@@ -266,9 +556,10 @@ interface Candidate {
     mask: array2d.Array2D<boolean>;
 }
 
+// Maps each placed cell to the key of the requirement placed there, so layouts that only swap requirements with equal keys look the same.
 function partsArr2DForGrid(
     grid: Grid,
-    reqs: Requirement[]
+    keys: number[]
 ): array2d.Array2D<number> {
     const partsArr2d = array2d.full(-1, grid.cells.nrows, grid.cells.ncols);
     for (let y = 0; y < grid.cells.nrows; ++y) {
@@ -277,7 +568,7 @@ function partsArr2DForGrid(
             if (v < 0) {
                 continue;
             }
-            partsArr2d[y * partsArr2d.ncols + x] = reqs[v].partIndex;
+            partsArr2d[y * partsArr2d.ncols + x] = keys[v];
         }
     }
     return partsArr2d;
@@ -291,13 +582,216 @@ function encodeMaskToString(mask: array2d.Array2D<boolean>): string {
     );
 }
 
-export function* solve(
+export type SolveError =
+    | { type: "partIndexOutOfRange"; requirementIndex: number }
+    | { type: "unsupportedRotationGroup" }
+    | { type: "tooManyPlacements"; placements: number; requirements: number };
+
+// Checks that every requirement refers to an existing part. solve and placeAll throw the returned error instead of failing deep inside the search.
+export function validateRequirements(
+    parts: Part[],
+    requirements: Requirement[]
+): SolveError | null {
+    for (let i = 0; i < requirements.length; ++i) {
+        const partIndex = requirements[i].partIndex;
+        if (
+            !Number.isInteger(partIndex) ||
+            partIndex < 0 ||
+            partIndex >= parts.length
+        ) {
+            return { type: "partIndexOutOfRange", requirementIndex: i };
+        }
+    }
+    return null;
+}
+
+export class SolutionIterator {
+    error: SolveError | null;
+    upperBound: number;
+    it: Iterator<Solution | null>;
+
+    constructor(
+        parts: Part[],
+        requirements: Requirement[],
+        gridSettings: GridSettings,
+        spinnableColors: boolean[],
+        options: SolveOptions = {},
+        candidateCache: Map<string, Candidate[]> | null = null
+    ) {
+        // The candidates are found once for the bound and kept in the cache for the search to reuse.
+        const cache = candidateCache || new Map<string, Candidate[]>();
+        this.error = validateRequirements(parts, requirements);
+        this.upperBound =
+            this.error === null
+                ? candidatesForRequirements(
+                      parts,
+                      requirements,
+                      gridSettings,
+                      spinnableColors,
+                      cache
+                  ).reduce((product, cands) => product * cands.length, 1)
+                : 0;
+        this.it = search(
+            parts,
+            requirements,
+            gridSettings,
+            spinnableColors,
+            options,
+            false,
+            cache
+        )[Symbol.iterator]();
+    }
+
+    next(): IteratorResult<Solution> {
+        while (true) {
+            const r = this.it.next();
+            if (r.done) {
+                return { done: true, value: undefined };
+            }
+            if (r.value !== null) {
+                return { done: false, value: r.value };
+            }
+        }
+    }
+
+    // Returns up to n solutions at once, for callers where each call is expensive. Fewer than n are only returned once there are no more.
+    nextChunk(n: number): Solution[] {
+        const solutions: Solution[] = [];
+        while (solutions.length < n) {
+            const r = this.next();
+            if (r.done) {
+                break;
+            }
+            solutions.push(r.value);
+        }
+        return solutions;
+    }
+
+    [Symbol.iterator]() {
+        return this;
+    }
+
+    // Returns the lower and upper bounds on the number of solutions. The upper bound is the product of the candidate counts, so is usually far larger than the actual count, and may be Infinity. Throws the same SolveError as next for invalid requirements.
+    sizeHint(): [number, number] {
+        if (this.error !== null) {
+            throw this.error;
+        }
+        return [0, this.upperBound];
+    }
+}
+
+export function solve(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[],
+    options: SolveOptions = {}
+): SolutionIterator {
+    return new SolutionIterator(
+        parts,
+        requirements,
+        gridSettings,
+        spinnableColors,
+        options
+    );
+}
+
+// Solves many sets of requirements against the same parts and grid, reusing the candidates found for each part and constraint across solves.
+export class Solver {
+    parts: Part[];
+    gridSettings: GridSettings;
+    spinnableColors: boolean[];
+    candidateCache: Map<string, Candidate[]>;
+
+    constructor(
+        parts: Part[],
+        gridSettings: GridSettings,
+        spinnableColors: boolean[]
+    ) {
+        this.parts = parts;
+        this.gridSettings = gridSettings;
+        this.spinnableColors = spinnableColors;
+        this.candidateCache = new Map();
+    }
+
+    solve(
+        requirements: Requirement[],
+        options: SolveOptions = {}
+    ): SolutionIterator {
+        return new SolutionIterator(
+            this.parts,
+            requirements,
+            this.gridSettings,
+            this.spinnableColors,
+            options,
+            this.candidateCache
+        );
+    }
+}
+
+export type StepResult =
+    | { type: "found"; solution: Solution }
+    | { type: "searching" }
+    | { type: "done" };
+
+export class SteppedSolver {
+    it: Iterator<Solution | null>;
+
+    constructor(
+        parts: Part[],
+        requirements: Requirement[],
+        gridSettings: GridSettings,
+        spinnableColors: boolean[],
+        options: SolveOptions = {}
+    ) {
+        this.it = search(
+            parts,
+            requirements,
+            gridSettings,
+            spinnableColors,
+            options,
+            true
+        )[Symbol.iterator]();
+    }
+
+    // Explores at most budget nodes of the search tree, stopping early if a solution is found.
+    step(budget: number): StepResult {
+        for (let i = 0; i < budget; ++i) {
+            const r = this.it.next();
+            if (r.done) {
+                return { type: "done" };
+            }
+            if (r.value !== null) {
+                return { type: "found", solution: r.value };
+            }
+        }
+        return { type: "searching" };
+    }
+}
+
+// If yieldNodes is set, null is yielded every time a candidate is tried so callers can bound the amount of work done between solutions.
+function* search(
     parts: Part[],
     requirements: Requirement[],
     gridSettings: GridSettings,
-    spinnableColors: boolean[]
-): Iterable<Solution> {
-    if (gridSettings.commandLineRow > gridSettings.height) {
+    spinnableColors: boolean[],
+    options: SolveOptions,
+    yieldNodes: boolean,
+    candidateCache: Map<string, Candidate[]> | null = null
+): Iterable<Solution | null> {
+    const error = validateRequirements(parts, requirements);
+    if (error !== null) {
+        throw error;
+    }
+
+    if (gridRotations(gridSettings) === null) {
+        throw { type: "unsupportedRotationGroup" } as SolveError;
+    }
+
+    if (
+        gridSettings.commandLineRules === undefined &&
+        gridSettings.commandLineRow > gridSettings.height
+    ) {
         return;
     }
 
@@ -306,115 +800,439 @@ export function* solve(
         return;
     }
 
-    const candidates = new Array<[number, Candidate[]]>(requirements.length);
-    for (let i = 0; i < requirements.length; ++i) {
-        const req = requirements[i];
-        const part = parts[req.partIndex];
-        candidates[i] = [
-            i,
-            candidatesForPart(
-                part,
-                gridSettings,
-                req.constraint,
-                spinnableColors[part.color] || false
-            ),
-        ];
+    const candidates = orderCandidates(
+        parts,
+        requirements,
+        candidatesForRequirements(
+            parts,
+            requirements,
+            gridSettings,
+            spinnableColors,
+            candidateCache
+        )
+    );
+
+    // A requirement that can't be placed anywhere can't be part of any solution.
+    if (candidates.some(([, cands]) => cands.length == 0)) {
+        return;
     }
 
-    // Heuristic: fit hard to fit blocks first, then easier ones.
-    //
-    // If two blocks are just as hard to fit, make sure to group ones of the same type together.
-    candidates.sort(([i, a], [j, b]) => {
-        const cmp = a.length - b.length;
-        if (cmp != 0) {
-            return cmp;
-        }
-        return i - j;
-    });
+    // For each candidate index, the fewest squares each remaining requirement that must be on the command line can occupy. Wildcards ignore the command line rules, so never have to be on it.
+    const remainingCommandLineSizes = new Array<number[]>(
+        candidates.length + 1
+    );
+    remainingCommandLineSizes[candidates.length] = [];
+    for (let i = candidates.length - 1; i >= 0; --i) {
+        const req = requirements[candidates[i][0]];
+        remainingCommandLineSizes[i] =
+            req.constraint.onCommandLine && !req.constraint.wildcard
+                ? [
+                      ...remainingCommandLineSizes[i + 1],
+                      minOccupiedSquares(parts[req.partIndex], req.constraint),
+                  ]
+                : remainingCommandLineSizes[i + 1];
+    }
+
+    // For each candidate index, the most squares the remaining requirements can occupy.
+    const remainingMaxSquares = new Array<number>(candidates.length + 1);
+    remainingMaxSquares[candidates.length] = 0;
+    for (let i = candidates.length - 1; i >= 0; --i) {
+        const req = requirements[candidates[i][0]];
+        remainingMaxSquares[i] =
+            remainingMaxSquares[i + 1] +
+            maxOccupiedSquares(parts[req.partIndex], req.constraint);
+    }
+
+    // The cells each required placement covers, and the requirements still left to place at each candidate index.
+    const containingCells = new Array<number[] | null>(
+        requirements.length
+    ).fill(null);
+    const containingCompressed = new Array<boolean>(requirements.length);
+    for (const [reqIdx, placement] of options.containing || []) {
+        containingCells[reqIdx] = placementCells(
+            parts[requirements[reqIdx].partIndex],
+            placement,
+            gridSettings
+        ).map(({ x, y }) => y * gridDimensions(gridSettings)[1] + x);
+        containingCompressed[reqIdx] = placement.compressed;
+    }
+    const remainingContaining = new Array<number[]>(candidates.length + 1);
+    remainingContaining[candidates.length] = [];
+    for (let i = candidates.length - 1; i >= 0; --i) {
+        const reqIdx = candidates[i][0];
+        remainingContaining[i] =
+            containingCells[reqIdx] !== null
+                ? [...remainingContaining[i + 1], reqIdx]
+                : remainingContaining[i + 1];
+    }
+
+    const stats: SolveStats = options.stats || {
+        nodes: 0,
+        nodeLimitHit: false,
+        depth: 0,
+        deepestRequirement: null,
+        firstLevelTried: 0,
+        firstLevelTotal: 0,
+    };
+    stats.nodes = 0;
+    stats.nodeLimitHit = false;
+    stats.depth = 0;
+    stats.deepestRequirement =
+        candidates.length > 0 ? candidates[0][0] : null;
+    stats.firstLevelTried = 0;
+    stats.firstLevelTotal = candidates.length > 0 ? candidates[0][1].length : 0;
+
+    // Requirements are told apart in visited layouts by their part, except those with distances to keep, since swapping them with another requirement for the same part can change whether the distances are kept.
+    const distanced = new Set<number>();
+    for (const [a, b] of options.minDistances || []) {
+        distanced.add(a);
+        distanced.add(b);
+    }
+    const layoutKeys = requirements.map((req, i) =>
+        distanced.has(i) ? parts.length + i : req.partIndex
+    );
 
     const visited = new Set();
+    const placedCompressed = new Array<boolean>(requirements.length);
+    const compressible = parts.map(isCompressible);
+    let compressedCount = 0;
+    let buggedCount = 0;
 
-    for (const raw of (function* helper(
+    for (const solution of (function* helper(
         grid: Grid,
         candidateIdx: number
-    ): Iterable<{ reqIdx: number; placement: Placement }[]> {
+    ): Iterable<Solution | null> {
+        if (candidateIdx > stats.depth) {
+            stats.depth = candidateIdx;
+            stats.deepestRequirement =
+                candidateIdx < candidates.length
+                    ? candidates[candidateIdx][0]
+                    : null;
+        }
+
+        // Solutions are filled in by requirement index as the recursion unwinds, so they don't need to be sorted afterwards.
         if (candidateIdx === candidates.length) {
-            yield [];
+            // Solutions are otherwise checked once their last placement is made, which never happens without requirements.
+            if (
+                candidates.length === 0 &&
+                !solutionIsAdmissible(
+                    parts,
+                    requirements,
+                    grid,
+                    placedCompressed,
+                    options
+                )
+            ) {
+                return;
+            }
+            yield new Array(requirements.length);
+            return;
+        }
+
+        if (
+            options.pruneCommandLine &&
+            !commandLineSpaceSuffices(
+                grid,
+                remainingCommandLineSizes[candidateIdx]
+            )
+        ) {
+            return;
+        }
+
+        if (
+            options.fillCommandLine &&
+            uncoveredCommandLineSquares(grid) >
+                remainingMaxSquares[candidateIdx]
+        ) {
             return;
         }
 
+        for (const i of remainingContaining[candidateIdx]) {
+            for (const j of containingCells[i] as number[]) {
+                if (grid.cells[j] != Cell.Empty) {
+                    return;
+                }
+            }
+        }
+
         const [reqIdx, cands] = candidates[candidateIdx];
+        const wantCells = containingCells[reqIdx];
         const req = requirements[reqIdx];
         const part = parts[req.partIndex];
 
         for (const candidate of cands) {
+            if (
+                options.maxNodes !== undefined &&
+                stats.nodes >= options.maxNodes
+            ) {
+                stats.nodeLimitHit = true;
+                return;
+            }
+            ++stats.nodes;
+            if (candidateIdx == 0) {
+                ++stats.firstLevelTried;
+            }
+
+            if (yieldNodes) {
+                yield null;
+            }
+
+            const compressed =
+                candidate.placement.compressed && compressible[req.partIndex];
+            if (
+                compressed &&
+                options.maxCompressed !== undefined &&
+                compressedCount >= options.maxCompressed
+            ) {
+                continue;
+            }
+
             if (
                 !grid.canPlace(candidate.mask, candidate.placement.loc.position)
             ) {
                 continue;
             }
 
-            const grid2 = grid.clone();
-            grid2.placeNoCheck(
+            const filled = grid.placeNoCheck(
                 candidate.mask,
                 candidate.placement.loc.position,
                 reqIdx
             );
 
             if (
+                wantCells !== null &&
+                (candidate.placement.compressed !=
+                    containingCompressed[reqIdx] ||
+                    filled.length != wantCells.length ||
+                    filled.some((v, i) => v != wantCells[i]))
+            ) {
+                grid.undo(filled);
+                continue;
+            }
+
+            if (
+                !req.constraint.wildcard &&
                 !placementIsAdmissible(
-                    grid2,
+                    grid,
                     part.isSolid,
                     reqIdx,
                     req.constraint.onCommandLine,
                     req.constraint.maxBugLevel
                 )
             ) {
+                grid.undo(filled);
                 continue;
             }
 
-            const gridByParts = String.fromCharCode(
-                ...partsArr2DForGrid(grid2, requirements)
+            // Bugs from being out of bounds or on the wrong side of the command line can't go away as more blocks are placed, so they already count against the budget.
+            const bugged =
+                options.maxBugs !== undefined &&
+                !req.constraint.wildcard &&
+                placementIsBugged(grid, part.isSolid, reqIdx);
+            if (bugged && buggedCount >= (options.maxBugs as number)) {
+                grid.undo(filled);
+                continue;
+            }
+
+            let gridByParts = String.fromCharCode(
+                ...partsArr2DForGrid(grid, layoutKeys)
             );
+            // The grid alone can't tell apart placements with identical compressed and uncompressed masks, so include their states where they are distinguished.
+            placedCompressed[reqIdx] = candidate.placement.compressed;
+            for (let i = 0; i <= candidateIdx; ++i) {
+                const j = candidates[i][0];
+                if (requirements[j].constraint.distinguishEqualCompression) {
+                    gridByParts += +placedCompressed[j];
+                }
+            }
             if (visited.has(gridByParts)) {
+                grid.undo(filled);
                 continue;
             }
             visited.add(gridByParts);
 
-            for (const solution of helper(grid2, candidateIdx + 1)) {
-                solution.push({ reqIdx, placement: candidate.placement });
+            buggedCount += +bugged;
+            compressedCount += +compressed;
+            for (const solution of helper(grid, candidateIdx + 1)) {
+                if (solution === null) {
+                    yield null;
+                    continue;
+                }
+                solution[reqIdx] = candidate.placement;
                 if (
                     candidateIdx === candidates.length - 1 &&
-                    !solutionIsAdmissible(parts, requirements, grid2)
+                    !solutionIsAdmissible(
+                        parts,
+                        requirements,
+                        grid,
+                        placedCompressed,
+                        options
+                    )
                 ) {
                     continue;
                 }
                 yield solution;
             }
+            compressedCount -= +compressed;
+            buggedCount -= +bugged;
+
+            grid.undo(filled);
         }
     })(new Grid(gridSettings), 0)) {
-        raw.sort(({ reqIdx: i }, { reqIdx: j }) => i - j);
-        const solution = new Array(raw.length);
-        for (let i = 0; i < raw.length; ++i) {
-            solution[i] = raw[i].placement;
-        }
         yield solution;
     }
 }
 
-function requirementsAreAdmissible(
+function requirementKey(parts: Part[], req: Requirement): string {
+    const part = parts[req.partIndex];
+    return JSON.stringify([
+        part.isSolid,
+        part.color,
+        part.compressedColor,
+        encodeMaskToString(part.compressedMask),
+        encodeMaskToString(part.uncompressedMask),
+        req.constraint,
+    ]);
+}
+
+// Pairs each requirement's candidates with its index, in the order the search tries to place them.
+function orderCandidates(
     parts: Part[],
     requirements: Requirement[],
-    gridSettings: GridSettings
-) {
-    // Mandatory check: blocks required to be on the command line must be less than or equal to the number of columns.
-    let commandLineParts = 0;
-    for (const req of requirements) {
-        if (req.constraint.onCommandLine) {
-            ++commandLineParts;
-        }
-    }
-    if (commandLineParts > gridSettings.width) {
+    candidatesPerRequirement: Candidate[][]
+): [number, Candidate[]][] {
+    const candidates = candidatesPerRequirement.map(
+        (cands, i): [number, Candidate[]] => [i, cands]
+    );
+
+    // Heuristic: fit hard to fit blocks first, then easier ones.
+    //
+    // If two blocks are just as hard to fit, order them by what they are rather than where they are in the requirements: this groups ones of the same type together, and keeps the order of solutions the same no matter how the requirements are ordered.
+    const keys = requirements.map((req) => requirementKey(parts, req));
+    candidates.sort(([i, a], [j, b]) => {
+        const cmp = a.length - b.length;
+        if (cmp != 0) {
+            return cmp;
+        }
+        if (keys[i] != keys[j]) {
+            return keys[i] < keys[j] ? -1 : 1;
+        }
+        return i - j;
+    });
+    return candidates;
+}
+
+// If a cache is given, candidates are looked up in and added to it by part and constraint, so it must only be used with the same parts, grid settings and spinnable colors.
+function candidatesForRequirements(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[],
+    cache: Map<string, Candidate[]> | null = null
+): Candidate[][] {
+    return requirements.map((req) => {
+        const key = JSON.stringify([req.partIndex, req.constraint]);
+        const cached = cache !== null ? cache.get(key) : undefined;
+        if (cached !== undefined) {
+            return cached;
+        }
+        const part = parts[req.partIndex];
+        const candidates = candidatesForPart(
+            part,
+            gridSettings,
+            req.constraint,
+            spinnableColors[part.color] || false
+        );
+        if (cache !== null) {
+            cache.set(key, candidates);
+        }
+        return candidates;
+    });
+}
+
+function minOccupiedSquares(part: Part, constraint: Constraint): number {
+    const compressedSquares = arrayCountTrue(part.compressedMask);
+    const uncompressedSquares = arrayCountTrue(part.uncompressedMask);
+    return constraint.compressed === true
+        ? compressedSquares
+        : constraint.compressed === false
+        ? uncompressedSquares
+        : Math.min(compressedSquares, uncompressedSquares);
+}
+
+function maxOccupiedSquares(part: Part, constraint: Constraint): number {
+    const compressedSquares = arrayCountTrue(part.compressedMask);
+    const uncompressedSquares = arrayCountTrue(part.uncompressedMask);
+    return constraint.compressed === true
+        ? compressedSquares
+        : constraint.compressed === false
+        ? uncompressedSquares
+        : Math.max(compressedSquares, uncompressedSquares);
+}
+
+// Counts the squares on the command line no part has been placed on, including forbidden ones that can never be covered.
+function uncoveredCommandLineSquares(grid: Grid): number {
+    let n = 0;
+    for (let y = 0; y < grid.cells.nrows; ++y) {
+        if (!grid.isOnCommandLine(y)) {
+            continue;
+        }
+        for (let x = 0; x < grid.cells.ncols; ++x) {
+            if (grid.cells[y * grid.cells.ncols + x] < 0) {
+                ++n;
+            }
+        }
+    }
+    return n;
+}
+
+function commandLineSpaceSuffices(grid: Grid, sizes: number[]) {
+    if (sizes.length == 0) {
+        return true;
+    }
+
+    // Every part on the command line needs its own empty square on it, and has to fit into an empty region touching it.
+    let commandLineSquares = 0;
+    let largestRegion = 0;
+    for (const region of grid.emptyRegions()) {
+        let n = 0;
+        for (const i of region) {
+            if (grid.isOnCommandLine(Math.floor(i / grid.cells.ncols))) {
+                ++n;
+            }
+        }
+        if (n > 0) {
+            commandLineSquares += n;
+            largestRegion = Math.max(largestRegion, region.length);
+        }
+    }
+
+    return (
+        sizes.length <= commandLineSquares &&
+        Math.max(...sizes) <= largestRegion
+    );
+}
+
+function requirementsAreAdmissible(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings
+) {
+    // Mandatory check: blocks required to be on the command line must be less than or equal to the number of columns.
+    let commandLineParts = 0;
+    for (const req of requirements) {
+        if (req.constraint.onCommandLine && !req.constraint.wildcard) {
+            ++commandLineParts;
+        }
+    }
+    const commandLineRows = commandLineRulesForGridSettings(
+        gridSettings
+    ).filter((rule) => rule === CommandLineRule.SolidPreferred).length;
+    const subResolution = gridSettings.subResolution || 1;
+    if (
+        commandLineParts >
+        gridSettings.width * commandLineRows * subResolution ** 2
+    ) {
         return false;
     }
 
@@ -428,9 +1246,9 @@ function requirementsAreAdmissible(
                 : part.uncompressedMask
         );
     }
-    const availableSquares =
-        gridSettings.width * gridSettings.height -
-        (gridSettings.hasOob ? 4 : 0);
+    const availableSquares = arrayCountTrue(
+        new Grid(gridSettings).cells.map((v) => v == Cell.Empty)
+    );
     if (occupiedSquares > availableSquares) {
         return false;
     }
@@ -441,19 +1259,29 @@ function requirementsAreAdmissible(
 interface PlacementDetail {
     outOfBounds: boolean;
     onCommandLine: boolean;
+    onNonSolidCommandLine: boolean;
     adjacentSameColoredPlacements: Set<number>;
 }
 
+// Returns the color the part shows in the given compression state.
+function effectiveColor(part: Part, compressed: boolean): number {
+    return compressed && part.compressedColor !== undefined
+        ? part.compressedColor
+        : part.color;
+}
+
 function resolvePlacementDetails(
     parts: Part[],
     requirements: Requirement[],
-    grid: Grid
+    grid: Grid,
+    compressed: boolean[]
 ) {
     const placementDetails: PlacementDetail[] = new Array(requirements.length);
     for (let i = 0; i < requirements.length; ++i) {
         placementDetails[i] = {
             outOfBounds: false,
             onCommandLine: false,
+            onNonSolidCommandLine: false,
             adjacentSameColoredPlacements: new Set(),
         };
     }
@@ -470,22 +1298,22 @@ function resolvePlacementDetails(
             const placementDetail = placementDetails[reqIdx];
 
             // Optional admissibility: check if a block has/doesn't have any out of bounds parts.
-            if (
-                grid.hasOob &&
-                (x === 0 ||
-                    y === 0 ||
-                    x === grid.cells.ncols - 1 ||
-                    y === grid.cells.nrows - 1)
-            ) {
+            if (grid.isOutOfBounds(x, y)) {
                 placementDetail.outOfBounds = true;
             }
 
             // Optional admissibility: check if a block is/isn't on the command line.
-            if (y === grid.commandLineRow) {
+            if (grid.isOnCommandLine(y)) {
                 placementDetail.onCommandLine = true;
             }
+            if (grid.isOnNonSolidCommandLine(y)) {
+                placementDetail.onNonSolidCommandLine = true;
+            }
 
-            // Optional admissibility: check if same-colored blocks are appropriately touching/not touching.
+            // Optional admissibility: check if same-colored blocks are appropriately touching/not touching. Wildcards have no color to share.
+            if (req.constraint.wildcard) {
+                continue;
+            }
             for (const [x2, y2] of [
                 [x - 1, y],
                 [x + 1, y],
@@ -502,17 +1330,7 @@ function resolvePlacementDetails(
                 }
 
                 // Ignore touching in out of bounds regions.
-                if (
-                    grid.hasOob &&
-                    (x == 0 ||
-                        y == 0 ||
-                        x == grid.cells.ncols - 1 ||
-                        y == grid.cells.nrows - 1) &&
-                    (x2 == 0 ||
-                        y2 == 0 ||
-                        x2 == grid.cells.ncols - 1 ||
-                        y2 == grid.cells.nrows - 1)
-                ) {
+                if (grid.isOutOfBounds(x, y) && grid.isOutOfBounds(x2, y2)) {
                     continue;
                 }
 
@@ -524,9 +1342,14 @@ function resolvePlacementDetails(
                 const neigborReq = requirements[neigborReqIdx];
                 const neighborPart = parts[neigborReq.partIndex];
 
+                if (neigborReq.constraint.wildcard) {
+                    continue;
+                }
+
                 if (
                     neigborReqIdx != reqIdx &&
-                    neighborPart.color === part.color
+                    effectiveColor(neighborPart, compressed[neigborReqIdx]) ===
+                        effectiveColor(part, compressed[reqIdx])
                 ) {
                     placementDetail.adjacentSameColoredPlacements.add(
                         neigborReqIdx
@@ -540,22 +1363,51 @@ function resolvePlacementDetails(
     return placementDetails;
 }
 
+function placementBugLevel(
+    grid: Grid,
+    parts: Part[],
+    req: Requirement,
+    placementDetail: PlacementDetail
+) {
+    if (req.constraint.wildcard) {
+        return 0;
+    }
+    const part = parts[req.partIndex];
+    return (
+        +placementDetail.outOfBounds +
+        +isCommandLineBugged(
+            grid,
+            part.isSolid,
+            placementDetail.onCommandLine,
+            placementDetail.onNonSolidCommandLine
+        ) +
+        placementDetail.adjacentSameColoredPlacements.size
+    );
+}
+
 function solutionIsAdmissible(
     parts: Part[],
     requirements: Requirement[],
-    grid: Grid
+    grid: Grid,
+    compressed: boolean[],
+    options: SolveOptions
 ) {
-    const placementDetails = resolvePlacementDetails(parts, requirements, grid);
+    const placementDetails = resolvePlacementDetails(
+        parts,
+        requirements,
+        grid,
+        compressed
+    );
 
+    let bugs = 0;
     for (let i = 0; i < placementDetails.length; ++i) {
-        const placementDetail = placementDetails[i];
         const req = requirements[i];
-        const part = parts[req.partIndex];
-
-        const bugLevel =
-            +placementDetail.outOfBounds +
-            +(part.isSolid === !placementDetail.onCommandLine) +
-            placementDetail.adjacentSameColoredPlacements.size;
+        const bugLevel = placementBugLevel(
+            grid,
+            parts,
+            req,
+            placementDetails[i]
+        );
 
         if (
             bugLevel > req.constraint.maxBugLevel ||
@@ -563,11 +1415,85 @@ function solutionIsAdmissible(
         ) {
             return false;
         }
+
+        if (bugLevel > 0) {
+            ++bugs;
+        }
+    }
+
+    // Optional admissibility: check the number of bugged blocks is within budget.
+    if (options.maxBugs !== undefined && bugs > options.maxBugs) {
+        return false;
+    }
+
+    // Optional admissibility: check the command line is completely covered.
+    if (options.fillCommandLine && uncoveredCommandLineSquares(grid) > 0) {
+        return false;
+    }
+
+    // Optional admissibility: check the given pairs of placements are far enough apart.
+    if (options.minDistances !== undefined) {
+        const cells = requirements.map(() => [] as Position[]);
+        for (let y = 0; y < grid.cells.nrows; ++y) {
+            for (let x = 0; x < grid.cells.ncols; ++x) {
+                const reqIdx = grid.cells[y * grid.cells.ncols + x];
+                if (reqIdx >= 0) {
+                    cells[reqIdx].push({ x, y });
+                }
+            }
+        }
+        for (const [a, b, k] of options.minDistances) {
+            for (const p of cells[a]) {
+                for (const q of cells[b]) {
+                    if (Math.abs(p.x - q.x) + Math.abs(p.y - q.y) < k) {
+                        return false;
+                    }
+                }
+            }
+        }
     }
 
     return true;
 }
 
+interface LonePlacementDetail {
+    isAllOob: boolean;
+    outOfBounds: boolean;
+    onCommandLine: boolean;
+    onNonSolidCommandLine: boolean;
+}
+
+function resolveLonePlacementDetail(
+    grid: Grid,
+    reqIdx: number
+): LonePlacementDetail {
+    const detail = {
+        isAllOob: true,
+        outOfBounds: false,
+        onCommandLine: false,
+        onNonSolidCommandLine: false,
+    };
+    for (let y = 0; y < grid.cells.nrows; ++y) {
+        for (let x = 0; x < grid.cells.ncols; ++x) {
+            if (grid.cells[y * grid.cells.ncols + x] !== reqIdx) {
+                continue;
+            }
+            if (grid.isOutOfBounds(x, y)) {
+                detail.outOfBounds = true;
+            } else {
+                detail.isAllOob = false;
+            }
+            if (grid.isOnCommandLine(y)) {
+                detail.onCommandLine = true;
+            }
+            if (grid.isOnNonSolidCommandLine(y)) {
+                detail.onNonSolidCommandLine = true;
+            }
+        }
+    }
+    return detail;
+}
+
 function placementIsAdmissible(
     grid: Grid,
     isSolid: boolean,
@@ -575,56 +1501,117 @@ function placementIsAdmissible(
     onCommandLine: boolean | null,
     maxBugLevel: number
 ) {
+    return (
+        placementRejection(
+            grid,
+            isSolid,
+            reqIdx,
+            onCommandLine,
+            maxBugLevel
+        ) === null
+    );
+}
+
+function placementRejection(
+    grid: Grid,
+    isSolid: boolean,
+    reqIdx: number,
+    onCommandLine: boolean | null,
+    maxBugLevel: number
+): PlacementRejection | null {
+    const {
+        isAllOob,
+        outOfBounds,
+        onCommandLine: placedOnCommandLine,
+        onNonSolidCommandLine: placedOnNonSolidCommandLine,
+    } = resolveLonePlacementDetail(grid, reqIdx);
+
     // Mandatory admissibility: ensure not everything is out of bounds.
-    if (grid.hasOob) {
-        let isAllOob = true;
-        top: for (let y = 1; y < grid.cells.nrows - 1; ++y) {
-            for (let x = 1; x < grid.cells.ncols - 1; ++x) {
-                const cell = grid.cells[y * grid.cells.ncols + x];
-                if (cell === reqIdx) {
-                    isAllOob = false;
-                    break top;
-                }
-            }
-        }
-        if (isAllOob) {
-            return false;
-        }
+    if (grid.hasOob && isAllOob) {
+        return "allOutOfBounds";
     }
 
-    // Optional admissibility: check if the block is appropriately in/out of bounds.
-    const outOfBounds =
-        grid.hasOob &&
-        (arrayCountNumber(array2d.row(grid.cells, 0), reqIdx) > 0 ||
-            arrayCountNumber(array2d.col(grid.cells, 0), reqIdx) > 0 ||
-            arrayCountNumber(
-                array2d.row(grid.cells, grid.cells.nrows - 1),
-                reqIdx
-            ) > 0 ||
-            arrayCountNumber(
-                array2d.col(grid.cells, grid.cells.ncols - 1),
-                reqIdx
-            ) > 0);
-
     // Optional admissibility: check if the block is appropriately on/off the command line.
-    const placedOnCommandLine =
-        arrayCountNumber(array2d.row(grid.cells, grid.commandLineRow), reqIdx) >
-        0;
-
     if (onCommandLine && !placedOnCommandLine) {
-        return false;
+        return "onCommandLineMismatch";
     }
 
     // It is not possible to tell if the bug level is less than the minimum bug level, because we might see more bugs later due to adjacent colors.
     // So here, we only check if we have too many bugs.
-    const bugLevel = +outOfBounds + +(isSolid === !placedOnCommandLine);
+    const bugLevel =
+        +outOfBounds +
+        +isCommandLineBugged(
+            grid,
+            isSolid,
+            placedOnCommandLine,
+            placedOnNonSolidCommandLine
+        );
     if (bugLevel > maxBugLevel) {
-        return false;
+        return "tooManyBugs";
     }
 
+    return null;
+}
+
+// Returns whether the placement is bugged on its own, not counting bugs from touching other parts.
+function placementIsBugged(grid: Grid, isSolid: boolean, reqIdx: number) {
+    const detail = resolveLonePlacementDetail(grid, reqIdx);
+    return (
+        detail.outOfBounds ||
+        isCommandLineBugged(
+            grid,
+            isSolid,
+            detail.onCommandLine,
+            detail.onNonSolidCommandLine
+        )
+    );
+}
+
+function placementIsWithinRegion(
+    grid: Grid,
+    reqIdx: number,
+    [topLeft, bottomRight]: [Position, Position]
+) {
+    for (let y = 0; y < grid.cells.nrows; ++y) {
+        for (let x = 0; x < grid.cells.ncols; ++x) {
+            if (grid.cells[y * grid.cells.ncols + x] !== reqIdx) {
+                continue;
+            }
+            if (
+                x < topLeft.x ||
+                y < topLeft.y ||
+                x > bottomRight.x ||
+                y > bottomRight.y
+            ) {
+                return false;
+            }
+        }
+    }
     return true;
 }
 
+// Whether the part's compressed mask differs from its uncompressed one. Compressing a part that isn't compressible changes nothing, so its compressed placements don't count as compressed.
+function isCompressible(part: Part): boolean {
+    return !array2d.equal(part.compressedMask, part.uncompressedMask);
+}
+
+function partMasksForConstraint(
+    part: Part,
+    constraint: Constraint
+): { mask: array2d.Array2D<boolean>; compressed: boolean }[] {
+    return constraint.compressed === false ||
+        (constraint.compressed === null &&
+            !constraint.distinguishEqualCompression &&
+            !isCompressible(part))
+        ? [{ mask: part.uncompressedMask, compressed: false }]
+        : constraint.compressed
+        ? [{ mask: part.compressedMask, compressed: true }]
+        : [
+              { mask: part.compressedMask, compressed: true },
+              { mask: part.uncompressedMask, compressed: false },
+          ];
+}
+
 function candidatesForPart(
     part: Part,
     gridSettings: GridSettings,
@@ -632,25 +1619,27 @@ function candidatesForPart(
     spinnable: boolean
 ): Candidate[] {
     const candidates: Candidate[] = [];
-    const partMasks =
-        constraint.compressed === false
-            ? [{ mask: part.uncompressedMask, compressed: false }]
-            : constraint.compressed ||
-              array2d.equal(part.compressedMask, part.uncompressedMask)
-            ? [{ mask: part.compressedMask, compressed: true }]
-            : [
-                  { mask: part.compressedMask, compressed: true },
-                  { mask: part.uncompressedMask, compressed: false },
-              ];
-    for (const { mask: partMask, compressed } of partMasks) {
+    for (const { mask: partMask, compressed } of partMasksForConstraint(
+        part,
+        constraint
+    )) {
         for (const { loc, mask } of placementLocationsAndMasksForMask(
             partMask,
             part.isSolid,
             gridSettings,
             constraint.onCommandLine,
             constraint.maxBugLevel,
+            constraint.region || null,
+            constraint.wildcard || false,
             spinnable
         )) {
+            if (
+                constraint.anchor !== undefined &&
+                (loc.position.x != constraint.anchor.x ||
+                    loc.position.y != constraint.anchor.y)
+            ) {
+                continue;
+            }
             candidates.push({ placement: { loc, compressed }, mask });
         }
     }
@@ -663,47 +1652,56 @@ function placementLocationsAndMasksForMask(
     gridSettings: GridSettings,
     onCommandLine: boolean | null,
     maxBugLevel: number,
+    region: [Position, Position] | null,
+    wildcard: boolean,
     spinnable: boolean
 ) {
     const locations: { loc: Location; mask: array2d.Array2D<boolean> }[] = [];
-    for (const position of placementPositionsForMask(
-        mask,
-        isSolid,
-        gridSettings,
-        onCommandLine,
-        maxBugLevel
-    )) {
-        locations.push({ loc: { position, rotation: 0 }, mask });
+    const rotatedMasks = distinctRotations(mask, gridSettings, spinnable);
+    for (let rotation = 0; rotation < rotatedMasks.length; ++rotation) {
+        const rotatedMask = rotatedMasks[rotation];
+        for (const position of placementPositionsForMask(
+            rotatedMask,
+            isSolid,
+            gridSettings,
+            onCommandLine,
+            maxBugLevel,
+            region,
+            wildcard
+        )) {
+            locations.push({
+                loc: { position, rotation },
+                mask: rotatedMask,
+            });
+        }
     }
 
-    if (spinnable) {
+    return locations;
+}
+
+// Returns the mask in each rotation it can be placed in, indexed by rotation, stopping once a rotation gives a shape already seen.
+function distinctRotations(
+    mask: array2d.Array2D<boolean>,
+    gridSettings: GridSettings,
+    spinnable: boolean
+): array2d.Array2D<boolean>[] {
+    const masks = [mask];
+    if (spinnable && !gridSettings.disableRotations) {
         const knownMasks = new Set();
         knownMasks.add(encodeMaskToString(trim(mask)));
 
-        for (let i = 1; i < 4; ++i) {
+        const rotations = gridRotations(gridSettings) as number;
+        for (let i = 1; i < rotations; ++i) {
             mask = array2d.rot90(mask);
             const knownMask = encodeMaskToString(trim(mask));
             if (knownMasks.has(knownMask)) {
                 break;
             }
             knownMasks.add(knownMask);
-
-            for (const position of placementPositionsForMask(
-                mask,
-                isSolid,
-                gridSettings,
-                onCommandLine,
-                maxBugLevel
-            )) {
-                locations.push({
-                    loc: { position, rotation: i },
-                    mask,
-                });
-            }
+            masks.push(mask);
         }
     }
-
-    return locations;
+    return masks;
 }
 
 function placementPositionsForMask(
@@ -711,62 +1709,2042 @@ function placementPositionsForMask(
     isSolid: boolean,
     gridSettings: GridSettings,
     onCommandLine: boolean | null,
-    maxBugLevel: number
+    maxBugLevel: number,
+    region: [Position, Position] | null,
+    wildcard: boolean
 ) {
-    const positions: Position[] = [];
+    return placementReasonsForMask(
+        mask,
+        isSolid,
+        gridSettings,
+        onCommandLine,
+        maxBugLevel,
+        region,
+        wildcard
+    )
+        .filter(([, reason]) => reason.type == "accepted")
+        .map(([pos]) => pos);
+}
+
+export type PlacementRejection =
+    | "clipped"
+    | "collision"
+    | "outsideRegion"
+    | "allOutOfBounds"
+    | "onCommandLineMismatch"
+    | "tooManyBugs";
+
+export type PlacementReason =
+    | { type: "accepted" }
+    | { type: "rejected"; rejection: PlacementRejection };
+
+function placementReasonsForMask(
+    mask: array2d.Array2D<boolean>,
+    isSolid: boolean,
+    gridSettings: GridSettings,
+    onCommandLine: boolean | null,
+    maxBugLevel: number,
+    region: [Position, Position] | null,
+    wildcard: boolean
+): [Position, PlacementReason][] {
+    const reasons: [Position, PlacementReason][] = [];
 
     for (let y = -mask.nrows + 1; y < mask.nrows; ++y) {
         for (let x = -mask.ncols + 1; x < mask.ncols; ++x) {
             const pos = { x, y };
             const grid = new Grid(gridSettings);
             if (!grid.canPlace(mask, pos)) {
+                // Placing it anyway tells apart cells falling off the board from cells covering forbidden ones.
+                const filled = grid.placeNoCheck(mask, pos, 0);
+                reasons.push([
+                    pos,
+                    {
+                        type: "rejected",
+                        rejection:
+                            filled.length < arrayCountTrue(mask)
+                                ? "clipped"
+                                : "collision",
+                    },
+                ]);
                 continue;
             }
             grid.placeNoCheck(mask, pos, 0);
 
-            if (
-                !placementIsAdmissible(
-                    grid,
-                    isSolid,
-                    0,
-                    onCommandLine,
-                    maxBugLevel
-                )
-            ) {
+            if (region !== null && !placementIsWithinRegion(grid, 0, region)) {
+                reasons.push([
+                    pos,
+                    { type: "rejected", rejection: "outsideRegion" },
+                ]);
                 continue;
             }
 
-            positions.push(pos);
+            // Wildcards may fill any empty cell, even one entirely out of bounds, so none of the rules apply to them.
+            const rejection = wildcard
+                ? null
+                : placementRejection(
+                      grid,
+                      isSolid,
+                      0,
+                      onCommandLine,
+                      maxBugLevel
+                  );
+            reasons.push([
+                pos,
+                rejection !== null
+                    ? { type: "rejected", rejection }
+                    : { type: "accepted" },
+            ]);
         }
     }
 
-    return positions;
+    return reasons;
 }
 
-export function placeAll(
+// Returns the mask of the part as placed, compressed or not and rotated. The mask is always a copy, so may be modified freely.
+export function orientedMask(
+    part: Part,
+    placement: Placement
+): array2d.Array2D<boolean> {
+    const mask = placement.compressed
+        ? part.compressedMask
+        : part.uncompressedMask;
+    const oriented = array2d.rotate(mask, placement.loc.rotation);
+    return oriented === mask ? array2d.copy(mask) : oriented;
+}
+
+function gridForPlacements(
     parts: Part[],
     requirements: Requirement[],
     placements: Placement[],
     gridSettings: GridSettings
-): (number | null)[] {
+): Grid {
     const grid = new Grid(gridSettings);
-    const cells = new Array(grid.cells.length);
-
     for (let i = 0; i < placements.length; ++i) {
         const req = requirements[i];
         const placement = placements[i];
         const part = parts[req.partIndex];
-        let mask = placement.compressed
-            ? part.compressedMask
-            : part.uncompressedMask;
-        for (let j = 0; j < placement.loc.rotation; ++j) {
-            mask = array2d.rot90(mask);
-        }
-        grid.placeNoCheck(mask, placement.loc.position, i);
+        grid.placeNoCheck(
+            orientedMask(part, placement),
+            placement.loc.position,
+            i
+        );
+    }
+    return grid;
+}
+
+// Returns the index of the requirement placed on each cell, or null for cells without one, including walls. Walls are returned by wallIds instead.
+export function placeAll(
+    parts: Part[],
+    requirements: Requirement[],
+    placements: Placement[],
+    gridSettings: GridSettings
+): (number | null)[] {
+    const error = validateRequirements(parts, requirements);
+    if (error !== null) {
+        throw error;
     }
 
+    // Placements are indexed by requirement, so any past the last requirement have nothing to place.
+    if (placements.length > requirements.length) {
+        throw {
+            type: "tooManyPlacements",
+            placements: placements.length,
+            requirements: requirements.length,
+        } as SolveError;
+    }
+
+    const grid = gridForPlacements(
+        parts,
+        requirements,
+        placements,
+        gridSettings
+    );
+    const cells = new Array(grid.cells.length);
     for (let i = 0; i < grid.cells.length; ++i) {
         cells[i] = grid.cells[i] < 0 ? null : grid.cells[i];
     }
     return cells;
 }
+
+// Returns the id of the wall on each cell, or null for cells without one.
+export function wallIds(gridSettings: GridSettings): (number | null)[] {
+    return [...new Grid(gridSettings).cells].map((v) =>
+        v < Cell.Forbidden ? cellWallId(v) : null
+    );
+}
+
+export function placeAll2D(
+    parts: Part[],
+    requirements: Requirement[],
+    placements: Placement[],
+    gridSettings: GridSettings
+): (number | null)[][] {
+    const cells = placeAll(parts, requirements, placements, gridSettings);
+    const [height, width] = gridDimensions(gridSettings);
+    const rows = new Array(height);
+    for (let y = 0; y < height; ++y) {
+        rows[y] = cells.slice(y * width, (y + 1) * width);
+    }
+    return rows;
+}
+
+function bestSolutionBy(
+    solutions: Iterable<Solution>,
+    score: (solution: Solution) => number
+): Solution | null {
+    let best: Solution | null = null;
+    let bestScore = -Infinity;
+    for (const solution of solutions) {
+        const s = score(solution);
+        if (s > bestScore) {
+            best = solution;
+            bestScore = s;
+        }
+    }
+    return best;
+}
+
+export function solveBestByCellWeight(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[],
+    cellWeights: number[]
+): Solution | null {
+    return bestSolutionBy(
+        solve(parts, requirements, gridSettings, spinnableColors),
+        (solution) => {
+            const cells = placeAll(parts, requirements, solution, gridSettings);
+            let score = 0;
+            for (let i = 0; i < cells.length; ++i) {
+                if (cells[i] !== null) {
+                    score += cellWeights[i] || 0;
+                }
+            }
+            return score;
+        }
+    );
+}
+
+export type PartError =
+    | { type: "dimensionMismatch" }
+    | { type: "emptyMask"; compressed: boolean }
+    | {
+          type: "compressedLarger";
+          compressedCells: number;
+          uncompressedCells: number;
+      }
+    | { type: "compressedNotSubset"; position: Position };
+
+// With strict, also requires each compressed cell to be an uncompressed cell once both masks are trimmed. The game's own data doesn't hold to this, as compressing can reshape a part, so it's off by default.
+export function validatePart(part: Part, strict = false): PartError | null {
+    for (const mask of [part.compressedMask, part.uncompressedMask]) {
+        if (mask.length != mask.nrows * mask.ncols) {
+            return { type: "dimensionMismatch" };
+        }
+    }
+    if (
+        part.compressedMask.nrows != part.uncompressedMask.nrows ||
+        part.compressedMask.ncols != part.uncompressedMask.ncols
+    ) {
+        return { type: "dimensionMismatch" };
+    }
+
+    const compressedCells = arrayCountTrue(part.compressedMask);
+    const uncompressedCells = arrayCountTrue(part.uncompressedMask);
+    if (compressedCells == 0) {
+        return { type: "emptyMask", compressed: true };
+    }
+    if (uncompressedCells == 0) {
+        return { type: "emptyMask", compressed: false };
+    }
+
+    // Compression may reshape a part entirely, so the compressed cells are not necessarily a subset of the uncompressed ones: unless strict, only check that compressing doesn't make it bigger.
+    if (compressedCells > uncompressedCells) {
+        return { type: "compressedLarger", compressedCells, uncompressedCells };
+    }
+
+    if (strict) {
+        const compressed = trim(part.compressedMask);
+        const uncompressed = trim(part.uncompressedMask);
+        for (let y = 0; y < compressed.nrows; ++y) {
+            for (let x = 0; x < compressed.ncols; ++x) {
+                if (
+                    compressed[y * compressed.ncols + x] &&
+                    !(
+                        y < uncompressed.nrows &&
+                        x < uncompressed.ncols &&
+                        uncompressed[y * uncompressed.ncols + x]
+                    )
+                ) {
+                    return { type: "compressedNotSubset", position: { x, y } };
+                }
+            }
+        }
+    }
+
+    return null;
+}
+
+export function validateParts(
+    parts: Part[],
+    strict = false
+): { partIndex: number; error: PartError }[] {
+    const errors: { partIndex: number; error: PartError }[] = [];
+    for (let i = 0; i < parts.length; ++i) {
+        const error = validatePart(parts[i], strict);
+        if (error !== null) {
+            errors.push({ partIndex: i, error });
+        }
+    }
+    return errors;
+}
+
+export function* solvePage(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[],
+    offset: number,
+    limit: number
+): Iterable<Solution> {
+    const it = solve(parts, requirements, gridSettings, spinnableColors)[
+        Symbol.iterator
+    ]();
+
+    for (let i = 0; i < offset; ++i) {
+        if (it.next().done) {
+            return;
+        }
+    }
+
+    for (let i = 0; i < limit; ++i) {
+        const r = it.next();
+        if (r.done) {
+            return;
+        }
+        yield r.value;
+    }
+}
+
+export function candidateTimings(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[]
+): [number, number][] {
+    const timings: [number, number][] = [];
+    for (let i = 0; i < requirements.length; ++i) {
+        const req = requirements[i];
+        const part = parts[req.partIndex];
+        const start = performance.now();
+        candidatesForPart(
+            part,
+            gridSettings,
+            req.constraint,
+            spinnableColors[part.color] || false
+        );
+        timings.push([i, performance.now() - start]);
+    }
+    return timings;
+}
+
+export function countAtLeast(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[],
+    k: number
+): boolean {
+    if (k <= 0) {
+        return true;
+    }
+    let n = 0;
+    for (const _ of solve(parts, requirements, gridSettings, spinnableColors)) {
+        if (++n >= k) {
+            return true;
+        }
+    }
+    return false;
+}
+
+// Decodes a mask packed one bit per cell, row-major, with the leftmost cell in the most significant bit and each row padded to a whole number of bytes.
+export function maskFromRomBytes(
+    bytes: ArrayLike<number>,
+    height: number,
+    width: number
+): array2d.Array2D<boolean> | null {
+    const bytesPerRow = Math.ceil(width / 8);
+    if (bytes.length != height * bytesPerRow) {
+        return null;
+    }
+
+    const mask = array2d.full(false, height, width);
+    for (let y = 0; y < height; ++y) {
+        for (let x = 0; x < width; ++x) {
+            const b = bytes[y * bytesPerRow + Math.floor(x / 8)];
+            mask[y * width + x] = ((b >> (7 - (x % 8))) & 1) == 1;
+        }
+    }
+    return mask;
+}
+
+export function placementCells(
+    part: Part,
+    placement: Placement,
+    gridSettings: GridSettings
+): Position[] {
+    const [height, width] = gridDimensions(gridSettings);
+    return maskCellIndexes(
+        orientedMask(part, placement),
+        placement.loc.position,
+        height,
+        width
+    ).map((i) => ({ x: i % width, y: Math.floor(i / width) }));
+}
+
+function occupiedBoundingBoxArea(
+    cells: (number | null)[],
+    gridSettings: GridSettings
+): number {
+    const [height, width] = gridDimensions(gridSettings);
+    let left = width;
+    let top = height;
+    let right = -1;
+    let bottom = -1;
+    for (let y = 0; y < height; ++y) {
+        for (let x = 0; x < width; ++x) {
+            if (cells[y * width + x] === null) {
+                continue;
+            }
+            left = Math.min(left, x);
+            top = Math.min(top, y);
+            right = Math.max(right, x);
+            bottom = Math.max(bottom, y);
+        }
+    }
+    if (right < 0) {
+        return 0;
+    }
+    return (right - left + 1) * (bottom - top + 1);
+}
+
+export function solveBestByCompactness(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[]
+): Solution | null {
+    return bestSolutionBy(
+        solve(parts, requirements, gridSettings, spinnableColors),
+        (solution) =>
+            -occupiedBoundingBoxArea(
+                placeAll(parts, requirements, solution, gridSettings),
+                gridSettings
+            )
+    );
+}
+
+export function solveContaining(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[],
+    containing: [number, Placement][]
+): Iterable<Solution> {
+    return solve(parts, requirements, gridSettings, spinnableColors, {
+        containing,
+    });
+}
+
+export type GridSettingsError =
+    | { type: "commandLineRowOutOfRange" }
+    | { type: "commandLineOnOobRow" }
+    | { type: "commandLineRulesLengthMismatch" };
+
+// Returns every problem with the grid settings, or an empty list if there are none.
+export function validateGridSettings(
+    gridSettings: GridSettings
+): GridSettingsError[] {
+    const errors: GridSettingsError[] = [];
+
+    // commandLineRow is unused when commandLineRules overrides it.
+    if (gridSettings.commandLineRules !== undefined) {
+        if (gridSettings.commandLineRules.length != gridSettings.height) {
+            errors.push({ type: "commandLineRulesLengthMismatch" });
+        }
+        return errors;
+    }
+
+    const commandLineRowInRange =
+        gridSettings.commandLineRow >= 0 &&
+        gridSettings.commandLineRow < gridSettings.height;
+    if (!commandLineRowInRange) {
+        errors.push({ type: "commandLineRowOutOfRange" });
+    }
+
+    // The top and bottom rows are out of bounds and contain the forbidden corners, so a command line there can never be fully used.
+    if (
+        commandLineRowInRange &&
+        gridSettings.hasOob &&
+        (gridSettings.commandLineRow == 0 ||
+            gridSettings.commandLineRow == gridSettings.height - 1)
+    ) {
+        errors.push({ type: "commandLineOnOobRow" });
+    }
+
+    return errors;
+}
+
+export function emptyCells(
+    parts: Part[],
+    requirements: Requirement[],
+    placements: Placement[],
+    gridSettings: GridSettings
+): Position[] {
+    const grid = gridForPlacements(
+        parts,
+        requirements,
+        placements,
+        gridSettings
+    );
+    const positions: Position[] = [];
+    for (let y = 0; y < grid.cells.nrows; ++y) {
+        for (let x = 0; x < grid.cells.ncols; ++x) {
+            if (grid.cells[y * grid.cells.ncols + x] == Cell.Empty) {
+                positions.push({ x, y });
+            }
+        }
+    }
+    return positions;
+}
+
+export interface SearchEstimate {
+    perRequirementCandidates: number[];
+    // The base 10 logarithm of the product of all candidate counts, which bounds the number of solutions if collisions are ignored. It's a logarithm as the product itself quickly grows past what a number can hold exactly. -Infinity if some requirement has no candidates.
+    log10ProductUpperBound: number;
+}
+
+export function searchEstimate(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[]
+): SearchEstimate {
+    const perRequirementCandidates = candidatesForRequirements(
+        parts,
+        requirements,
+        gridSettings,
+        spinnableColors
+    ).map((cands) => cands.length);
+    let log10ProductUpperBound = 0;
+    for (const n of perRequirementCandidates) {
+        log10ProductUpperBound += Math.log10(n);
+    }
+    return { perRequirementCandidates, log10ProductUpperBound };
+}
+
+export function rotationCount(placements: Placement[]): number {
+    let n = 0;
+    for (const placement of placements) {
+        if (placement.loc.rotation != 0) {
+            ++n;
+        }
+    }
+    return n;
+}
+
+export function solveBestByRotations(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[]
+): Solution | null {
+    return bestSolutionBy(
+        solve(parts, requirements, gridSettings, spinnableColors),
+        (solution) => -rotationCount(solution)
+    );
+}
+
+export interface PlacementInfo {
+    loc: Location;
+    compressed: boolean;
+    // Whether the placement is bugged on its own, not counting bugs from touching other parts.
+    bugged: boolean;
+}
+
+export function allPlacements(
+    part: Part,
+    gridSettings: GridSettings,
+    constraint: Constraint,
+    spinnable: boolean
+): PlacementInfo[] {
+    return candidatesForPart(part, gridSettings, constraint, spinnable).map(
+        ({ placement, mask }) => {
+            const grid = new Grid(gridSettings);
+            grid.placeNoCheck(mask, placement.loc.position, 0);
+            return {
+                loc: placement.loc,
+                compressed: placement.compressed,
+                bugged:
+                    !constraint.wildcard &&
+                    placementIsBugged(grid, part.isSolid, 0),
+            };
+        }
+    );
+}
+
+function buggedPlacementCount(
+    parts: Part[],
+    requirements: Requirement[],
+    solution: Solution,
+    gridSettings: GridSettings
+): number {
+    const grid = gridForPlacements(
+        parts,
+        requirements,
+        solution,
+        gridSettings
+    );
+    const placementDetails = resolvePlacementDetails(
+        parts,
+        requirements,
+        grid,
+        solution.map((placement) => placement.compressed)
+    );
+    let n = 0;
+    for (let i = 0; i < placementDetails.length; ++i) {
+        if (
+            placementBugLevel(
+                grid,
+                parts,
+                requirements[i],
+                placementDetails[i]
+            ) > 0
+        ) {
+            ++n;
+        }
+    }
+    return n;
+}
+
+export function solveMinBugs(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[]
+): Solution | null {
+    let best: Solution | null = null;
+    let bestBugs = Infinity;
+    for (const solution of solve(
+        parts,
+        requirements,
+        gridSettings,
+        spinnableColors
+    )) {
+        const bugs = buggedPlacementCount(
+            parts,
+            requirements,
+            solution,
+            gridSettings
+        );
+        if (bugs < bestBugs) {
+            best = solution;
+            bestBugs = bugs;
+        }
+        // Nothing can beat a solution without bugs.
+        if (bestBugs == 0) {
+            break;
+        }
+    }
+    return best;
+}
+
+// Returns the number of placements of each color, as [color, count] pairs sorted by color. Wildcards have no color, so aren't counted.
+export function colorHistogram(
+    parts: Part[],
+    requirements: Requirement[],
+    placements: Placement[]
+): [number, number][] {
+    const counts = new Map<number, number>();
+    for (let i = 0; i < placements.length; ++i) {
+        if (requirements[i].constraint.wildcard) {
+            continue;
+        }
+        const color = effectiveColor(
+            parts[requirements[i].partIndex],
+            placements[i].compressed
+        );
+        counts.set(color, (counts.get(color) || 0) + 1);
+    }
+    return [...counts.entries()].sort(([a], [b]) => a - b);
+}
+
+export function* solveFiltered(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[],
+    predicate: (solution: Solution) => boolean
+): Iterable<Solution> {
+    for (const solution of solve(
+        parts,
+        requirements,
+        gridSettings,
+        spinnableColors
+    )) {
+        if (predicate(solution)) {
+            yield solution;
+        }
+    }
+}
+
+// Groups the empty cells of an occupancy grid, as returned by placeAll, into orthogonally connected regions.
+export function emptyRegions(
+    occupancy: (number | null)[],
+    gridSettings: GridSettings
+): Position[][] {
+    const grid = new Grid(gridSettings);
+    for (let i = 0; i < occupancy.length; ++i) {
+        const v = occupancy[i];
+        if (v !== null) {
+            grid.cells[i] = v;
+        }
+    }
+    return grid
+        .emptyRegions()
+        .map((region) =>
+            region.map((i) => ({
+                x: i % grid.cells.ncols,
+                y: Math.floor(i / grid.cells.ncols),
+            }))
+        );
+}
+
+export function candidateOrder(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[]
+): number[] {
+    return orderCandidates(
+        parts,
+        requirements,
+        candidatesForRequirements(
+            parts,
+            requirements,
+            gridSettings,
+            spinnableColors
+        )
+    ).map(([reqIdx]) => reqIdx);
+}
+
+// Returns the cells occupied by compressed placements and by uncompressed placements, in that order.
+export function areaBreakdown(
+    parts: Part[],
+    requirements: Requirement[],
+    placements: Placement[]
+): [number, number] {
+    let compressedCells = 0;
+    let uncompressedCells = 0;
+    for (let i = 0; i < placements.length; ++i) {
+        const part = parts[requirements[i].partIndex];
+        if (placements[i].compressed) {
+            compressedCells += arrayCountTrue(part.compressedMask);
+        } else {
+            uncompressedCells += arrayCountTrue(part.uncompressedMask);
+        }
+    }
+    return [compressedCells, uncompressedCells];
+}
+
+export interface Conflict {
+    // The requirement already occupying the cell, then the one placed over it.
+    requirementIndices: [number, number];
+    position: Position;
+}
+
+// Places every placement in order, reporting each cell covered by more than one of them instead of stopping at the first.
+export function placeAllConflicts(
+    parts: Part[],
+    requirements: Requirement[],
+    placements: Placement[],
+    gridSettings: GridSettings
+): Conflict[] {
+    const [height, width] = gridDimensions(gridSettings);
+    const owners = new Array<number | null>(height * width).fill(null);
+    const conflicts: Conflict[] = [];
+    for (let i = 0; i < placements.length; ++i) {
+        const part = parts[requirements[i].partIndex];
+        for (const position of placementCells(
+            part,
+            placements[i],
+            gridSettings
+        )) {
+            const j = position.y * width + position.x;
+            const owner = owners[j];
+            if (owner !== null) {
+                conflicts.push({ requirementIndices: [owner, i], position });
+                continue;
+            }
+            owners[j] = i;
+        }
+    }
+    return conflicts;
+}
+
+export function solveBestByColorClustering(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[]
+): Solution | null {
+    return bestSolutionBy(
+        solve(parts, requirements, gridSettings, spinnableColors),
+        (solution) => {
+            const grid = gridForPlacements(
+                parts,
+                requirements,
+                solution,
+                gridSettings
+            );
+            let score = 0;
+            for (const placementDetail of resolvePlacementDetails(
+                parts,
+                requirements,
+                grid,
+                solution.map((placement) => placement.compressed)
+            )) {
+                score += placementDetail.adjacentSameColoredPlacements.size;
+            }
+            return score;
+        }
+    );
+}
+
+// Returns the requirements not yet placed in current that have exactly one placement left that fits on the board and is admissible on its own.
+export function forcedPlacements(
+    parts: Part[],
+    requirements: Requirement[],
+    current: [number, Placement][],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[]
+): [number, Placement][] {
+    const grid = new Grid(gridSettings);
+    const placed = new Array<boolean>(requirements.length).fill(false);
+    for (const [reqIdx, placement] of current) {
+        const part = parts[requirements[reqIdx].partIndex];
+        grid.placeNoCheck(
+            orientedMask(part, placement),
+            placement.loc.position,
+            reqIdx
+        );
+        placed[reqIdx] = true;
+    }
+
+    const candidatesPerRequirement = candidatesForRequirements(
+        parts,
+        requirements,
+        gridSettings,
+        spinnableColors
+    );
+    const forced: [number, Placement][] = [];
+    for (let reqIdx = 0; reqIdx < requirements.length; ++reqIdx) {
+        if (placed[reqIdx]) {
+            continue;
+        }
+        const req = requirements[reqIdx];
+        const part = parts[req.partIndex];
+
+        let only: Placement | null = null;
+        let count = 0;
+        for (const candidate of candidatesPerRequirement[reqIdx]) {
+            if (
+                !grid.canPlace(candidate.mask, candidate.placement.loc.position)
+            ) {
+                continue;
+            }
+            const filled = grid.placeNoCheck(
+                candidate.mask,
+                candidate.placement.loc.position,
+                reqIdx
+            );
+            const admissible = placementIsAdmissible(
+                grid,
+                part.isSolid,
+                reqIdx,
+                req.constraint.onCommandLine,
+                req.constraint.maxBugLevel
+            );
+            grid.undo(filled);
+            if (!admissible) {
+                continue;
+            }
+            only = candidate.placement;
+            if (++count > 1) {
+                break;
+            }
+        }
+        if (count == 1) {
+            forced.push([reqIdx, only as Placement]);
+        }
+    }
+    return forced;
+}
+
+// Exports a board as a row-major grid of bytes, one per cell: 0 for a cell without a part, or the part index plus one for a cell covered by a placement of that part. Returns null if there are too many parts to fit in a byte.
+export function exportNative(
+    parts: Part[],
+    requirements: Requirement[],
+    placements: Placement[],
+    gridSettings: GridSettings
+): Uint8Array | null {
+    if (parts.length > 255) {
+        return null;
+    }
+    const error = validateRequirements(parts, requirements);
+    if (error !== null) {
+        throw error;
+    }
+
+    const [height, width] = gridDimensions(gridSettings);
+    const bytes = new Uint8Array(height * width);
+    for (let i = 0; i < placements.length; ++i) {
+        const partIndex = requirements[i].partIndex;
+        for (const { x, y } of placementCells(
+            parts[partIndex],
+            placements[i],
+            gridSettings
+        )) {
+            bytes[y * width + x] = partIndex + 1;
+        }
+    }
+    return bytes;
+}
+
+// Recovers the placements of a board exported with exportNative. Going through the cells in row-major order, each cell not yet accounted for is given to the first unused requirement for its part whose placement covers it. Returns null if a cell can't be given to any requirement, or if a requirement is left unused.
+export function importNative(
+    parts: Part[],
+    requirements: Requirement[],
+    bytes: Uint8Array,
+    gridSettings: GridSettings
+): Placement[] | null {
+    const [height, width] = gridDimensions(gridSettings);
+    if (bytes.length != height * width) {
+        return null;
+    }
+
+    const anyConstraint: Constraint = {
+        compressed: null,
+        onCommandLine: null,
+        minBugLevel: 0,
+        maxBugLevel: Infinity,
+    };
+    const claimed = new Array<boolean>(bytes.length).fill(false);
+    const placements = new Array<Placement | undefined>(
+        requirements.length
+    ).fill(undefined);
+    for (let i = 0; i < bytes.length; ++i) {
+        if (bytes[i] == 0 || claimed[i]) {
+            continue;
+        }
+        const partIndex = bytes[i] - 1;
+        const reqIdx = requirements.findIndex(
+            (req, j) =>
+                req.partIndex == partIndex && placements[j] === undefined
+        );
+        if (reqIdx == -1) {
+            return null;
+        }
+
+        let cells: number[] = [];
+        const candidate = candidatesForPart(
+            parts[partIndex],
+            gridSettings,
+            {
+                ...anyConstraint,
+                distinguishEqualCompression:
+                    requirements[reqIdx].constraint
+                        .distinguishEqualCompression,
+            },
+            true
+        ).find(({ placement, mask }) => {
+            cells = maskCellIndexes(
+                mask,
+                placement.loc.position,
+                height,
+                width
+            );
+            // Earlier cells are all claimed or belong to other parts, so the placement has to start here.
+            return (
+                cells[0] == i &&
+                cells.every((j) => bytes[j] == bytes[i] && !claimed[j])
+            );
+        });
+        if (candidate === undefined) {
+            return null;
+        }
+        for (const j of cells) {
+            claimed[j] = true;
+        }
+        placements[reqIdx] = candidate.placement;
+    }
+    if (placements.some((placement) => placement === undefined)) {
+        return null;
+    }
+    return placements as Placement[];
+}
+
+export interface MirrorSymmetry {
+    // Unchanged when mirrored left to right.
+    horizontal: boolean;
+    // Unchanged when mirrored top to bottom.
+    vertical: boolean;
+}
+
+export function mirrorSymmetry(
+    mask: array2d.Array2D<boolean>
+): MirrorSymmetry {
+    const trimmed = trim(mask);
+
+    const mirrored = array2d.copy(trimmed);
+    array2d.flipRowsInplace(mirrored);
+
+    // Turning it upside down and then mirroring it left to right mirrors it top to bottom.
+    const flipped = array2d.rotate(trimmed, 2);
+    array2d.flipRowsInplace(flipped);
+
+    return {
+        horizontal: array2d.equal(trimmed, mirrored),
+        vertical: array2d.equal(trimmed, flipped),
+    };
+}
+
+export interface BugStatus {
+    outOfBounds: boolean;
+    // A solid part off the command line, or a non-solid part on it.
+    commandLineBugged: boolean;
+    touchingSameColor: boolean;
+}
+
+export function placementBugReport(
+    parts: Part[],
+    requirements: Requirement[],
+    placements: Placement[],
+    gridSettings: GridSettings
+): BugStatus[] {
+    const grid = gridForPlacements(
+        parts,
+        requirements,
+        placements,
+        gridSettings
+    );
+    return resolvePlacementDetails(
+        parts,
+        requirements,
+        grid,
+        placements.map((placement) => placement.compressed)
+    ).map(
+        (placementDetail, i) =>
+            requirements[i].constraint.wildcard
+                ? {
+                      outOfBounds: false,
+                      commandLineBugged: false,
+                      touchingSameColor: false,
+                  }
+                : {
+                      outOfBounds: placementDetail.outOfBounds,
+                      commandLineBugged: isCommandLineBugged(
+                          grid,
+                          parts[requirements[i].partIndex].isSolid,
+                          placementDetail.onCommandLine,
+                          placementDetail.onNonSolidCommandLine
+                      ),
+                      touchingSameColor:
+                          placementDetail.adjacentSameColoredPlacements.size >
+                          0,
+                  }
+    );
+}
+
+function placementsEqual(a: Placement, b: Placement) {
+    return (
+        a.compressed == b.compressed &&
+        a.loc.rotation == b.loc.rotation &&
+        a.loc.position.x == b.loc.position.x &&
+        a.loc.position.y == b.loc.position.y
+    );
+}
+
+// Yields every solution, those sharing the most placements with template first. As all solutions need to be ranked, nothing is yielded until the search is complete.
+export function* solveClosestTo(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[],
+    template: Placement[]
+): Iterable<Solution> {
+    const scored = [
+        ...solve(parts, requirements, gridSettings, spinnableColors),
+    ].map((solution): [Solution, number] => {
+        let matches = 0;
+        for (let i = 0; i < solution.length; ++i) {
+            if (
+                template[i] !== undefined &&
+                placementsEqual(solution[i], template[i])
+            ) {
+                ++matches;
+            }
+        }
+        return [solution, matches];
+    });
+    scored.sort(([, a], [, b]) => b - a);
+    for (const [solution] of scored) {
+        yield solution;
+    }
+}
+
+export interface CellDetail {
+    requirementIndex: number;
+    partIndex: number;
+    color: number;
+    compressed: boolean;
+    bugged: boolean;
+}
+
+export function solutionGridDetailed(
+    parts: Part[],
+    requirements: Requirement[],
+    placements: Placement[],
+    gridSettings: GridSettings
+): (CellDetail | null)[] {
+    const grid = gridForPlacements(
+        parts,
+        requirements,
+        placements,
+        gridSettings
+    );
+    const placementDetails = resolvePlacementDetails(
+        parts,
+        requirements,
+        grid,
+        placements.map((placement) => placement.compressed)
+    );
+    return placeAll(parts, requirements, placements, gridSettings).map((v) => {
+        if (v === null) {
+            return null;
+        }
+        const partIndex = requirements[v].partIndex;
+        const part = parts[partIndex];
+        return {
+            requirementIndex: v,
+            partIndex,
+            color: effectiveColor(part, placements[v].compressed),
+            compressed: placements[v].compressed,
+            bugged:
+                placementBugLevel(
+                    grid,
+                    parts,
+                    requirements[v],
+                    placementDetails[v]
+                ) > 0,
+        };
+    });
+}
+
+export interface SolveArgs {
+    parts: Part[];
+    requirements: Requirement[];
+    gridSettings: GridSettings;
+    spinnableColors: boolean[];
+}
+
+// Solves each puzzle in turn, returning up to limitPer solutions for each.
+export function solveBatch(
+    puzzles: SolveArgs[],
+    limitPer: number
+): Solution[][] {
+    return puzzles.map(
+        ({ parts, requirements, gridSettings, spinnableColors }) => [
+            ...solvePage(
+                parts,
+                requirements,
+                gridSettings,
+                spinnableColors,
+                0,
+                limitPer
+            ),
+        ]
+    );
+}
+
+export enum QualityMetric {
+    FewestBugs,
+    FewestRotations,
+    MostCompact,
+}
+
+function solutionCost(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    solution: Solution,
+    metric: QualityMetric
+): number {
+    switch (metric) {
+        case QualityMetric.FewestBugs:
+            return buggedPlacementCount(
+                parts,
+                requirements,
+                solution,
+                gridSettings
+            );
+        case QualityMetric.FewestRotations:
+            return rotationCount(solution);
+        case QualityMetric.MostCompact:
+            return occupiedBoundingBoxArea(
+                placeAll(parts, requirements, solution, gridSettings),
+                gridSettings
+            );
+    }
+}
+
+// Returns the n best solutions by metric, best first. Only the best n seen so far are kept while searching, and solutions that are just as good are kept in the order they were found.
+export function solveTopN(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[],
+    n: number,
+    metric: QualityMetric
+): Solution[] {
+    if (n <= 0) {
+        return [];
+    }
+    const best: [Solution, number][] = [];
+    for (const solution of solve(
+        parts,
+        requirements,
+        gridSettings,
+        spinnableColors
+    )) {
+        const cost = solutionCost(
+            parts,
+            requirements,
+            gridSettings,
+            solution,
+            metric
+        );
+        if (best.length == n && cost >= best[n - 1][1]) {
+            continue;
+        }
+
+        let lo = 0;
+        let hi = best.length;
+        while (lo < hi) {
+            const mid = (lo + hi) >> 1;
+            if (best[mid][1] <= cost) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        best.splice(lo, 0, [solution, cost]);
+        if (best.length > n) {
+            best.pop();
+        }
+    }
+    return best.map(([solution]) => solution);
+}
+
+// Returns whether the occupancy grids, as returned by placeAll, are the same under some rotation or reflection of the board that leaves its forbidden cells, walls, out of bounds area and command line rules where they are.
+export function solutionsEquivalent(
+    a: (number | null)[],
+    b: (number | null)[],
+    gridSettings: GridSettings
+): boolean {
+    const grid = new Grid(gridSettings);
+    const { nrows, ncols } = grid.cells;
+    if (a.length != nrows * ncols || b.length != nrows * ncols) {
+        return false;
+    }
+
+    const kinds = new Array<string>(grid.cells.length);
+    for (let y = 0; y < nrows; ++y) {
+        for (let x = 0; x < ncols; ++x) {
+            const v = grid.cells[y * ncols + x];
+            kinds[y * ncols + x] = [
+                v,
+                grid.isOutOfBounds(x, y),
+                grid.commandLineRules[Math.floor(y / grid.subResolution)],
+            ].join();
+        }
+    }
+
+    const indices = array2d.from(
+        [...Array(nrows * ncols).keys()],
+        nrows,
+        ncols
+    );
+    for (let rotation = 0; rotation < ROTATIONS; ++rotation) {
+        for (const mirrored of [false, true]) {
+            // Each cell of the transformed board holds the index of the cell it came from.
+            const sources = array2d.copy(array2d.rotate(indices, rotation));
+            if (mirrored) {
+                array2d.flipRowsInplace(sources);
+            }
+            if (sources.nrows != nrows || sources.ncols != ncols) {
+                continue;
+            }
+            if (
+                sources.every((j, i) => kinds[j] == kinds[i]) &&
+                sources.every((j, i) => a[j] === b[i])
+            ) {
+                return true;
+            }
+        }
+    }
+    return false;
+}
+
+export enum CellKind {
+    Empty,
+    Placed,
+    Forbidden,
+    Wall,
+}
+
+export function gridCells(
+    occupancy: (number | null)[],
+    gridSettings: GridSettings
+): CellKind[] {
+    const grid = new Grid(gridSettings);
+    return grid.cells.map((v, i) => {
+        const occupant = occupancy[i];
+        if (occupant !== undefined && occupant !== null) {
+            return CellKind.Placed;
+        }
+        if (v == Cell.Forbidden) {
+            return CellKind.Forbidden;
+        }
+        if (v < Cell.Forbidden) {
+            return CellKind.Wall;
+        }
+        return CellKind.Empty;
+    });
+}
+
+export type Diagnosis =
+    | SolveError
+    | { type: "requirementsInadmissible" }
+    | { type: "requirementUnplaceable"; requirementIndex: number };
+
+// Returns why there are known to be no solutions without searching for them, or null if there might be some.
+export function diagnose(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[]
+): Diagnosis | null {
+    const error = validateRequirements(parts, requirements);
+    if (error !== null) {
+        return error;
+    }
+
+    if (gridRotations(gridSettings) === null) {
+        return { type: "unsupportedRotationGroup" };
+    }
+
+    if (!requirementsAreAdmissible(parts, requirements, gridSettings)) {
+        return { type: "requirementsInadmissible" };
+    }
+
+    const requirementIndex = candidatesForRequirements(
+        parts,
+        requirements,
+        gridSettings,
+        spinnableColors
+    ).findIndex((cands) => cands.length == 0);
+    if (requirementIndex != -1) {
+        return { type: "requirementUnplaceable", requirementIndex };
+    }
+
+    return null;
+}
+
+// Resolves each requirement's part name against partsByName, preferring it to any part index given. Returns null if a name is unknown or a requirement refers to no part at all.
+export function resolveNames(
+    requirements: NamedRequirement[],
+    partsByName: { [name: string]: number }
+): Requirement[] | null {
+    const resolved: Requirement[] = [];
+    for (const { partIndex, partName, constraint } of requirements) {
+        if (partName !== undefined) {
+            if (!Object.prototype.hasOwnProperty.call(partsByName, partName)) {
+                return null;
+            }
+            resolved.push({ partIndex: partsByName[partName], constraint });
+        } else if (partIndex !== undefined) {
+            resolved.push({ partIndex, constraint });
+        } else {
+            return null;
+        }
+    }
+    return resolved;
+}
+
+// Returns a single cell part for wildcard requirements to refer to.
+export function wildcardPart(gridSettings: GridSettings): Part {
+    const [height, width] = gridDimensions(gridSettings);
+    const mask = array2d.full(false, height, width);
+    mask[0] = true;
+    return {
+        isSolid: false,
+        color: -1,
+        compressedMask: mask,
+        uncompressedMask: mask,
+    };
+}
+
+// Returns why the part could or couldn't be placed at every position and rotation tried when listing its candidates.
+export function placementReasons(
+    part: Part,
+    gridSettings: GridSettings,
+    constraint: Constraint,
+    spinnable: boolean
+): [Placement, PlacementReason][] {
+    const reasons: [Placement, PlacementReason][] = [];
+    for (const { mask, compressed } of partMasksForConstraint(
+        part,
+        constraint
+    )) {
+        const rotatedMasks = distinctRotations(mask, gridSettings, spinnable);
+        for (let rotation = 0; rotation < rotatedMasks.length; ++rotation) {
+            for (const [position, reason] of placementReasonsForMask(
+                rotatedMasks[rotation],
+                part.isSolid,
+                gridSettings,
+                constraint.onCommandLine,
+                constraint.maxBugLevel,
+                constraint.region || null,
+                constraint.wildcard || false
+            )) {
+                reasons.push([
+                    { loc: { position, rotation }, compressed },
+                    reason,
+                ]);
+            }
+        }
+    }
+    return reasons;
+}
+
+// Everything a solve is run with, for reproducing it elsewhere.
+export interface SolveConfig extends SolveArgs {
+    options: SolveOptions;
+}
+
+export function solveConfigToJson(config: SolveConfig): string {
+    // The stats are what a solve reports back, not what it is run with.
+    const options = { ...config.options };
+    delete options.stats;
+
+    // JSON would drop the dimensions of masks and turn unbounded bug levels into null, so both are written out explicitly.
+    return JSON.stringify({ ...config, options }, (key, value) => {
+        if (Array.isArray(value) && "nrows" in value && "ncols" in value) {
+            const arr2d = value as array2d.Array2D<boolean>;
+            return {
+                nrows: arr2d.nrows,
+                ncols: arr2d.ncols,
+                cells: [...arr2d],
+            };
+        }
+        if (value === Infinity) {
+            return "Infinity";
+        }
+        return value;
+    });
+}
+
+export function solveConfigFromJson(json: string): SolveConfig {
+    return JSON.parse(json, (key, value) => {
+        if (
+            value !== null &&
+            typeof value == "object" &&
+            Array.isArray(value.cells) &&
+            typeof value.nrows == "number" &&
+            typeof value.ncols == "number"
+        ) {
+            return array2d.from(value.cells, value.nrows, value.ncols);
+        }
+        if (value === "Infinity") {
+            return Infinity;
+        }
+        return value;
+    });
+}
+
+// Returns how many cells of each row and of each column are covered by parts.
+export function rowColDensity(
+    occupancy: (number | null)[],
+    gridSettings: GridSettings
+): [number[], number[]] {
+    const [height, width] = gridDimensions(gridSettings);
+    const rows = new Array<number>(height).fill(0);
+    const cols = new Array<number>(width).fill(0);
+    for (let y = 0; y < height; ++y) {
+        for (let x = 0; x < width; ++x) {
+            const v = occupancy[y * width + x];
+            if (v === null) {
+                continue;
+            }
+            ++rows[y];
+            ++cols[x];
+        }
+    }
+    return [rows, cols];
+}
+
+// Returns the number of empty cells in regions too small to fit even the smallest part not used by any requirement. If every part is used, all empty cells are wasted.
+export function wastedSpace(
+    parts: Part[],
+    requirements: Requirement[],
+    placements: Placement[],
+    gridSettings: GridSettings
+): number {
+    const used = new Set(requirements.map((req) => req.partIndex));
+    let smallestUnused = Infinity;
+    for (let i = 0; i < parts.length; ++i) {
+        if (used.has(i)) {
+            continue;
+        }
+        smallestUnused = Math.min(
+            smallestUnused,
+            arrayCountTrue(parts[i].compressedMask),
+            arrayCountTrue(parts[i].uncompressedMask)
+        );
+    }
+
+    const grid = gridForPlacements(
+        parts,
+        requirements,
+        placements,
+        gridSettings
+    );
+    let wasted = 0;
+    for (const region of grid.emptyRegions()) {
+        if (region.length < smallestUnused) {
+            wasted += region.length;
+        }
+    }
+    return wasted;
+}
+
+// Returns every position the mask fits on the empty grid, before filtering out ones that aren't admissible.
+export function rawPositions(
+    mask: array2d.Array2D<boolean>,
+    gridSettings: GridSettings
+): Position[] {
+    const grid = new Grid(gridSettings);
+    const positions: Position[] = [];
+    for (let y = -mask.nrows + 1; y < mask.nrows; ++y) {
+        for (let x = -mask.ncols + 1; x < mask.ncols; ++x) {
+            if (grid.canPlace(mask, { x, y })) {
+                positions.push({ x, y });
+            }
+        }
+    }
+    return positions;
+}
+
+// Returns the number of sides of filled cells that border an empty cell or the edge of the mask.
+export function maskPerimeter(mask: array2d.Array2D<boolean>): number {
+    let perimeter = 0;
+    for (let y = 0; y < mask.nrows; ++y) {
+        for (let x = 0; x < mask.ncols; ++x) {
+            if (!mask[y * mask.ncols + x]) {
+                continue;
+            }
+            for (const [x2, y2] of [
+                [x - 1, y],
+                [x + 1, y],
+                [x, y - 1],
+                [x, y + 1],
+            ]) {
+                if (
+                    x2 < 0 ||
+                    x2 >= mask.ncols ||
+                    y2 < 0 ||
+                    y2 >= mask.nrows ||
+                    !mask[y2 * mask.ncols + x2]
+                ) {
+                    ++perimeter;
+                }
+            }
+        }
+    }
+    return perimeter;
+}
+
+// Returns the solution whose solid parts cover the most command line, where each cell is worth the weight of its row.
+export function solveBestByCommandLineUsage(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[],
+    commandLineWeights: number[]
+): Solution | null {
+    const [height, width] = gridDimensions(gridSettings);
+    const subResolution = gridSettings.subResolution || 1;
+    return bestSolutionBy(
+        solve(parts, requirements, gridSettings, spinnableColors),
+        (solution) => {
+            const cells = placeAll(parts, requirements, solution, gridSettings);
+            let score = 0;
+            for (let y = 0; y < height; ++y) {
+                const weight =
+                    commandLineWeights[Math.floor(y / subResolution)] || 0;
+                for (let x = 0; x < width; ++x) {
+                    const v = cells[y * width + x];
+                    if (
+                        v !== null &&
+                        parts[requirements[v].partIndex].isSolid
+                    ) {
+                        score += weight;
+                    }
+                }
+            }
+            return score;
+        }
+    );
+}
+
+// Returns the mask moved to the top-left of a mask of the given size, or null if it doesn't fit.
+function resizeMask(
+    mask: array2d.Array2D<boolean>,
+    nrows: number,
+    ncols: number
+): array2d.Array2D<boolean> | null {
+    const trimmed = trim(mask);
+    if (trimmed.nrows > nrows || trimmed.ncols > ncols) {
+        return null;
+    }
+    const resized = array2d.full(false, nrows, ncols);
+    for (let y = 0; y < trimmed.nrows; ++y) {
+        for (let x = 0; x < trimmed.ncols; ++x) {
+            resized[y * ncols + x] = trimmed[y * trimmed.ncols + x];
+        }
+    }
+    return resized;
+}
+
+// Returns the height and width of the smallest square board the requirements can be solved on, trying sizes up to maxSize. Boards are tried without out of bounds cells and with the command line in the middle row, as in game.
+export function minBoardDimensions(
+    parts: Part[],
+    requirements: Requirement[],
+    spinnableColors: boolean[],
+    maxSize: number
+): [number, number] | null {
+    for (let size = 1; size <= maxSize; ++size) {
+        const resizedParts: Part[] = [];
+        const resizedRequirements: Requirement[] = [];
+        for (const req of requirements) {
+            const part = parts[req.partIndex];
+            const compressedMask = resizeMask(part.compressedMask, size, size);
+            const uncompressedMask = resizeMask(
+                part.uncompressedMask,
+                size,
+                size
+            );
+
+            // Only the masks the requirement may use need to fit.
+            let compressed = req.constraint.compressed;
+            if (compressedMask === null && compressed !== true) {
+                compressed = false;
+            }
+            if (uncompressedMask === null && compressed !== false) {
+                compressed = true;
+            }
+            if (
+                (compressed === true && compressedMask === null) ||
+                (compressed === false && uncompressedMask === null)
+            ) {
+                break;
+            }
+
+            const emptyMask = array2d.full(false, size, size);
+            resizedParts.push({
+                ...part,
+                compressedMask: compressedMask ?? emptyMask,
+                uncompressedMask: uncompressedMask ?? emptyMask,
+            });
+            resizedRequirements.push({
+                partIndex: resizedParts.length - 1,
+                constraint: { ...req.constraint, compressed },
+            });
+        }
+        if (resizedRequirements.length < requirements.length) {
+            continue;
+        }
+
+        const gridSettings: GridSettings = {
+            height: size,
+            width: size,
+            hasOob: false,
+            commandLineRow: Math.floor(size / 2),
+        };
+        const found = !solve(
+            resizedParts,
+            resizedRequirements,
+            gridSettings,
+            spinnableColors
+        ).next().done;
+        if (found) {
+            return [size, size];
+        }
+    }
+    return null;
+}
+
+// Returns the unique pairs of requirement indexes whose placed parts share an edge, each with the lower index first.
+export function adjacencyGraph(
+    parts: Part[],
+    requirements: Requirement[],
+    placements: Placement[],
+    gridSettings: GridSettings
+): [number, number][] {
+    const [height, width] = gridDimensions(gridSettings);
+    const cells = placeAll(parts, requirements, placements, gridSettings);
+    const seen = new Set<string>();
+    const edges: [number, number][] = [];
+    for (let y = 0; y < height; ++y) {
+        for (let x = 0; x < width; ++x) {
+            const v = cells[y * width + x];
+            if (v === null) {
+                continue;
+            }
+            for (const [nx, ny] of [
+                [x + 1, y],
+                [x, y + 1],
+            ]) {
+                if (nx >= width || ny >= height) {
+                    continue;
+                }
+                const w = cells[ny * width + nx];
+                if (w === null || w == v) {
+                    continue;
+                }
+                const edge: [number, number] = [Math.min(v, w), Math.max(v, w)];
+                const key = edge.join(",");
+                if (!seen.has(key)) {
+                    seen.add(key);
+                    edges.push(edge);
+                }
+            }
+        }
+    }
+    return edges;
+}
+
+export type ValidationError =
+    | SolveError
+    | { type: "invalidPart"; partIndex: number; error: PartError }
+    | { type: "invalidGridSettings"; error: GridSettingsError }
+    | { type: "maskSizeMismatch"; partIndex: number }
+    | { type: "colorOutOfRange"; partIndex: number };
+
+// Collects every problem with the arguments instead of stopping at the first, so a form can show them all at once.
+export function validateArgs(args: SolveArgs): ValidationError[] {
+    const { parts, requirements, gridSettings, spinnableColors } = args;
+    const errors: ValidationError[] = [];
+
+    // Parts only used by wildcard requirements, like wildcardPart, have no real color.
+    const coloredParts = new Set(
+        requirements
+            .filter((req) => !req.constraint.wildcard)
+            .map((req) => req.partIndex)
+    );
+    const wildcardParts = new Set(
+        requirements
+            .filter((req) => req.constraint.wildcard)
+            .map((req) => req.partIndex)
+    );
+
+    const [height, width] = gridDimensions(gridSettings);
+    for (let i = 0; i < parts.length; ++i) {
+        const part = parts[i];
+        const error = validatePart(part);
+        if (error !== null) {
+            errors.push({ type: "invalidPart", partIndex: i, error });
+        } else if (
+            part.compressedMask.nrows != height ||
+            part.compressedMask.ncols != width
+        ) {
+            errors.push({ type: "maskSizeMismatch", partIndex: i });
+        }
+        const colors =
+            part.compressedColor !== undefined
+                ? [part.color, part.compressedColor]
+                : [part.color];
+        if (
+            (coloredParts.has(i) || !wildcardParts.has(i)) &&
+            colors.some(
+                (color) =>
+                    !Number.isInteger(color) ||
+                    color < 0 ||
+                    color >= spinnableColors.length
+            )
+        ) {
+            errors.push({ type: "colorOutOfRange", partIndex: i });
+        }
+    }
+
+    for (let i = 0; i < requirements.length; ++i) {
+        if (validateRequirements(parts, [requirements[i]]) !== null) {
+            errors.push({ type: "partIndexOutOfRange", requirementIndex: i });
+        }
+    }
+
+    for (const error of validateGridSettings(gridSettings)) {
+        errors.push({ type: "invalidGridSettings", error });
+    }
+    if (gridRotations(gridSettings) === null) {
+        errors.push({ type: "unsupportedRotationGroup" });
+    }
+
+    return errors;
+}
+
+// Groups the part's admissible placements by rotation, in increasing rotation order. Placements are returned rather than locations since a location may be admissible both compressed and uncompressed.
+export function placementsByRotation(
+    part: Part,
+    gridSettings: GridSettings,
+    constraint: Constraint,
+    spinnable: boolean
+): [number, Placement[]][] {
+    const groups = new Map<number, Placement[]>();
+    for (const { placement } of candidatesForPart(
+        part,
+        gridSettings,
+        constraint,
+        spinnable
+    )) {
+        const rotation = placement.loc.rotation;
+        let group = groups.get(rotation);
+        if (group === undefined) {
+            group = [];
+            groups.set(rotation, group);
+        }
+        group.push(placement);
+    }
+    return [...groups.entries()].sort(([a], [b]) => a - b);
+}
+
+// Remembers every solution it has let through, so solutions repeated across several solves are only passed on the first time they are seen.
+export class SolutionDeduper {
+    seen: Set<string>;
+
+    constructor() {
+        this.seen = new Set();
+    }
+
+    *filter(solutions: Iterable<Solution>): Iterable<Solution> {
+        for (const solution of solutions) {
+            const key = JSON.stringify(solution);
+            if (this.seen.has(key)) {
+                continue;
+            }
+            this.seen.add(key);
+            yield solution;
+        }
+    }
+}
+
+// Returns the empty cells of an occupancy grid, as returned by placeAll, that are connected through empty cells to an empty cell on the command line.
+export function commandLineReachableCells(
+    occupancy: (number | null)[],
+    gridSettings: GridSettings
+): Position[] {
+    const grid = new Grid(gridSettings);
+    for (let i = 0; i < occupancy.length; ++i) {
+        const v = occupancy[i];
+        if (v !== null) {
+            grid.cells[i] = v;
+        }
+    }
+    const positions: Position[] = [];
+    for (const region of grid.emptyRegions()) {
+        if (
+            !region.some((i) =>
+                grid.isOnCommandLine(Math.floor(i / grid.cells.ncols))
+            )
+        ) {
+            continue;
+        }
+        for (const i of region) {
+            positions.push({
+                x: i % grid.cells.ncols,
+                y: Math.floor(i / grid.cells.ncols),
+            });
+        }
+    }
+    return positions;
+}
+
+export interface ScoreWeights {
+    bugs: number;
+    rotations: number;
+    compressed: number;
+    // Applied to the area of the bounding box of the occupied cells.
+    compactness: number;
+    // Penalties as [color, color, penalty], added for each pair of touching placements of those colors in either order. Wildcards have no color, so never add any.
+    colorTouchPenalties?: [number, number, number][];
+}
+
+// Returns a weighted sum of the solution's bugged placements, rotated placements, compressed placements of compressible parts and occupied bounding box area. Lower scores are better, like the costs used by solveTopN.
+export function scoreSolution(
+    parts: Part[],
+    requirements: Requirement[],
+    placements: Placement[],
+    gridSettings: GridSettings,
+    weights: ScoreWeights
+): number {
+    const cost = (metric: QualityMetric) =>
+        solutionCost(parts, requirements, gridSettings, placements, metric);
+    let score =
+        weights.bugs * cost(QualityMetric.FewestBugs) +
+        weights.rotations * cost(QualityMetric.FewestRotations) +
+        weights.compressed *
+            placements.filter(
+                (placement, i) =>
+                    placement.compressed &&
+                    isCompressible(parts[requirements[i].partIndex])
+            ).length +
+        weights.compactness * cost(QualityMetric.MostCompact);
+
+    if (weights.colorTouchPenalties !== undefined) {
+        const penalties = new Map<string, number>();
+        for (const [a, b, penalty] of weights.colorTouchPenalties) {
+            penalties.set([Math.min(a, b), Math.max(a, b)].join(), penalty);
+        }
+        const color = (reqIdx: number) =>
+            effectiveColor(
+                parts[requirements[reqIdx].partIndex],
+                placements[reqIdx].compressed
+            );
+        for (const [i, j] of adjacencyGraph(
+            parts,
+            requirements,
+            placements,
+            gridSettings
+        )) {
+            if (
+                requirements[i].constraint.wildcard ||
+                requirements[j].constraint.wildcard
+            ) {
+                continue;
+            }
+            const [a, b] = [color(i), color(j)];
+            score +=
+                penalties.get([Math.min(a, b), Math.max(a, b)].join()) || 0;
+        }
+    }
+
+    return score;
+}
+
+// Returns the distinct rotations the part can be placed in, compressed or not, each with its mask trimmed of empty rows and columns.
+export function orientedMasks(
+    part: Part,
+    gridSettings: GridSettings,
+    spinnable: boolean,
+    compressed: boolean
+): [number, array2d.Array2D<boolean>][] {
+    return distinctRotations(
+        compressed ? part.compressedMask : part.uncompressedMask,
+        gridSettings,
+        spinnable
+    ).map((mask, rotation): [number, array2d.Array2D<boolean>] => [
+        rotation,
+        trim(mask),
+    ]);
+}
+
+// Returns the first solution, retrying with every color spinnable if there is none as given, along with whether the retry was needed to find it. If neither finds a solution, the solution is null and the retry isn't counted as needed.
+export function solveOrSpin(
+    parts: Part[],
+    requirements: Requirement[],
+    gridSettings: GridSettings,
+    spinnableColors: boolean[]
+): [boolean, Solution | null] {
+    const r = solve(parts, requirements, gridSettings, spinnableColors).next();
+    if (!r.done) {
+        return [false, r.value];
+    }
+    if (spinnableColors.every((spinnable) => spinnable)) {
+        return [false, null];
+    }
+    const spun = solve(
+        parts,
+        requirements,
+        gridSettings,
+        spinnableColors.map(() => true)
+    ).next();
+    return spun.done ? [false, null] : [true, spun.value];
+}
+
+// Collapses identical requirements into one, returning the unique requirements and, for each original requirement, the index of its unique requirement. Results for the unique requirements can be expanded back with expandDeduped.
+export function dedupRequirements(
+    requirements: Requirement[]
+): [Requirement[], number[]] {
+    const unique: Requirement[] = [];
+    const indexes = new Map<string, number>();
+    const mapping = requirements.map((req) => {
+        const key = JSON.stringify([req.partIndex, req.constraint]);
+        let i = indexes.get(key);
+        if (i === undefined) {
+            i = unique.length;
+            indexes.set(key, i);
+            unique.push(req);
+        }
+        return i;
+    });
+    return [unique, mapping];
+}
+
+// Expands values found for each unique requirement from dedupRequirements, like their candidate counts, back to one for each original requirement.
+export function expandDeduped<T>(values: T[], mapping: number[]): T[] {
+    return mapping.map((i) => values[i]);
+}
+
+export interface ExportedCandidate {
+    placement: Placement;
+    // Indexes into the grid, in row-major order, of the cells the placement covers.
+    cells: number[];
+}
+
+// Returns each requirement's candidate placements along with the cells they cover, enough to pose the puzzle as an exact cover problem to another solver. Bug rules involving more than one placement still have to be checked on the solutions found.
+export function exportCandidates(args: SolveArgs): ExportedCandidate[][] {
+    const { parts, requirements, gridSettings, spinnableColors } = args;
+    const error = validateRequirements(parts, requirements);
+    if (error !== null) {
+        throw error;
+    }
+
+    if (gridRotations(gridSettings) === null) {
+        throw { type: "unsupportedRotationGroup" } as SolveError;
+    }
+
+    const [height, width] = gridDimensions(gridSettings);
+    return candidatesForRequirements(
+        parts,
+        requirements,
+        gridSettings,
+        spinnableColors
+    ).map((cands) =>
+        cands.map(({ placement, mask }) => ({
+            placement,
+            cells: maskCellIndexes(mask, placement.loc.position, height, width),
+        }))
+    );
+}
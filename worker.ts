@@ -1,33 +1,36 @@
-import { GridSettings, Part, Requirement, Solution, solve } from "./solver";
+/// <reference types="vite/client" />
+
+import {
+    Solution,
+    SolutionIterator,
+    SolveArgs,
+    SolveError,
+    solve,
+} from "./solver";
 
 export type Request =
     | { type: "next" }
-    | {
-          type: "init";
-          args: {
-              parts: Part[];
-              requirements: Requirement[];
-              gridSettings: GridSettings;
-              spinnableColors: boolean[];
-          };
-      };
+    | { type: "nextChunk"; n: number }
+    | { type: "init"; args: SolveArgs };
 
 export type Response =
     | { type: "ready" }
     | ({ type: "next" } & ({ done: true } | { done: false; value: Solution }))
-    | { type: "error"; reason: String };
+    | { type: "nextChunk"; values: Solution[] }
+    | { type: "error"; reason: String }
+    | { type: "solveError"; error: SolveError };
 
-let it: Iterator<Solution> | null = null;
+let it: SolutionIterator | null = null;
 
 self.onmessage = function (e: MessageEvent<Request>) {
-    console.time(e.data.type);
+    if (import.meta.env.DEV) {
+        console.time(e.data.type);
+    }
     switch (e.data.type) {
         case "init": {
             const { parts, requirements, gridSettings, spinnableColors } =
                 e.data.args;
-            it = solve(parts, requirements, gridSettings, spinnableColors)[
-                Symbol.iterator
-            ]();
+            it = solve(parts, requirements, gridSettings, spinnableColors);
             break;
         }
 
@@ -39,12 +42,41 @@ self.onmessage = function (e: MessageEvent<Request>) {
                 });
                 break;
             }
-            const r = it.next();
+            let r: IteratorResult<Solution>;
+            try {
+                r = it.next();
+            } catch (error) {
+                it = null;
+                self.postMessage({ type: "solveError", error } as Response);
+                break;
+            }
             self.postMessage({ type: "next", ...r } as Response);
             break;
         }
+
+        case "nextChunk": {
+            if (it === null) {
+                self.postMessage({
+                    type: "error",
+                    reason: "solver not initialized",
+                });
+                break;
+            }
+            let values: Solution[];
+            try {
+                values = it.nextChunk(e.data.n);
+            } catch (error) {
+                it = null;
+                self.postMessage({ type: "solveError", error } as Response);
+                break;
+            }
+            self.postMessage({ type: "nextChunk", values } as Response);
+            break;
+        }
+    }
+    if (import.meta.env.DEV) {
+        console.timeEnd(e.data.type);
     }
-    console.timeEnd(e.data.type);
 };
 
 self.postMessage({ type: "ready" } as Response);